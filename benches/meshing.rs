@@ -0,0 +1,65 @@
+use bevy::prelude::*;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use rand::prelude::*;
+
+use voxel::block::{blocks, BlockRegistry};
+use voxel::geometry::chunk::Chunk;
+use voxel::geometry::mesher::ChunkMesher;
+use voxel::geometry::padded::PaddedChunk;
+use voxel::world::generation::{NoiseTerrainGenerator, TerrainGenerator};
+
+const SIZES: [usize; 3] = [16, 32, 64];
+
+/**
+ * The chunks the meshers are benchmarked on, from the best case to the worst one
+ */
+fn bench_chunks(size: usize) -> Vec<(&'static str, PaddedChunk)> {
+  let mut rng = StdRng::seed_from_u64(15);
+
+  let empty = Chunk::new(size);
+  let mut full = Chunk::new(size);
+  let mut checkerboard = Chunk::new(size);
+  let mut random = Chunk::new(size);
+
+  for ((x, y, z), _) in empty.iter() {
+    full.set(x, y, z, blocks::STONE);
+    if (x + y + z) % 2 == 0 {
+      checkerboard.set(x, y, z, blocks::STONE);
+    }
+    random.set(x, y, z, *[blocks::AIR, blocks::STONE, blocks::DIRT].choose(&mut rng).unwrap());
+  }
+
+  // the chunk right below the surface at the origin, with some caves and every surface block
+  let terrain = NoiseTerrainGenerator::default().generate(IVec3::ZERO, size);
+
+  vec![
+    ("empty", PaddedChunk::from_chunk(&empty)),
+    ("full", PaddedChunk::from_chunk(&full)),
+    ("checkerboard", PaddedChunk::from_chunk(&checkerboard)),
+    ("random", PaddedChunk::from_chunk(&random)),
+    ("terrain", PaddedChunk::from_chunk(&terrain)),
+  ]
+}
+
+fn meshing(c: &mut Criterion) {
+  let registry = BlockRegistry::default();
+
+  for size in SIZES {
+    let mut group = c.benchmark_group(format!("mesh {size}"));
+    group.throughput(Throughput::Elements((size * size * size) as u64));
+
+    for (name, chunk) in bench_chunks(size) {
+      // the naive mesher is only there as a reference, it is too slow to be worth tracking
+      for mesher in ChunkMesher::all().into_iter().skip(1) {
+        group.bench_with_input(BenchmarkId::new(mesher.0.name(), name), &chunk, |b, chunk| {
+          b.iter(|| mesher.0.mesh(chunk, &registry))
+        });
+      }
+    }
+
+    group.finish();
+  }
+}
+
+criterion_group!(benches, meshing);
+criterion_main!(benches);
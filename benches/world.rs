@@ -0,0 +1,63 @@
+use bevy::prelude::*;
+use criterion::{criterion_group, criterion_main, Criterion};
+
+use voxel::block::{blocks, BlockRegistry};
+use voxel::geometry::mesher::ChunkMesher;
+use voxel::world::chunkmap::{ChunkMap, CHUNK_SIZE};
+use voxel::world::generation::{NoiseTerrainGenerator, TerrainGenerator};
+
+fn generation(c: &mut Criterion) {
+  let generator = NoiseTerrainGenerator::default();
+  let mut group = c.benchmark_group("generate");
+
+  // above the terrain (skipped columns), on the surface, and underground
+  for (name, coord) in [("sky", IVec3::new(0, 4, 0)), ("surface", IVec3::ZERO), ("underground", IVec3::new(0, -3, 0))] {
+    group.bench_function(name, |b| b.iter(|| generator.generate(coord, CHUNK_SIZE)));
+  }
+
+  group.finish();
+}
+
+/**
+ * The work done on the main thread and in the mesh task when a single voxel is edited
+ */
+fn remeshing(c: &mut Criterion) {
+  let registry = BlockRegistry::default();
+  let generator = NoiseTerrainGenerator::default();
+  let mesher = ChunkMesher::default();
+
+  let mut chunk_map = ChunkMap::new();
+  for x in -1..=1 {
+    for y in -1..=1 {
+      for z in -1..=1 {
+        let coord = IVec3::new(x, y, z);
+        chunk_map.insert(coord, generator.generate(coord, CHUNK_SIZE));
+      }
+    }
+  }
+
+  let mut group = c.benchmark_group("remesh");
+
+  group.bench_function("padded chunk", |b| b.iter(|| chunk_map.padded_chunk(IVec3::ZERO)));
+
+  // an edit in the corner of a chunk dirties 8 chunks
+  group.bench_function("edit corner", |b| {
+    let mut block = blocks::STONE;
+    b.iter(|| {
+      block = if block == blocks::STONE { blocks::AIR } else { blocks::STONE };
+      chunk_map.set_voxel(IVec3::ZERO, block);
+
+      let dirty: Vec<IVec3> = chunk_map.dirty_chunks().collect();
+      for coord in dirty {
+        chunk_map.clear_dirty(coord);
+        let padded = chunk_map.padded_chunk(coord).unwrap();
+        mesher.0.mesh(&padded, &registry);
+      }
+    })
+  });
+
+  group.finish();
+}
+
+criterion_group!(benches, generation, remeshing);
+criterion_main!(benches);
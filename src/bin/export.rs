@@ -0,0 +1,161 @@
+/*!
+ * Exports the chunks around a point of the world without opening a window
+ *
+ * usage: `export <file.obj|file.glb> [--center x y z] [--radius n] [--vertical-radius n] [--world directory] [--mesher name]`
+ * - `--center`: the chunk the export is centered on, `0 0 0` by default
+ * - `--radius`, `--vertical-radius`: how many chunks are exported around the center, horizontally and vertically
+ * - `--world`: the directory of a saved world, chunks that were never saved are generated, by default every chunk is generated
+ * - `--mesher`: the name of the mesher (e.g. `greedy`), the default mesher of the game otherwise
+ */
+
+use std::env;
+use std::path::PathBuf;
+use std::process::ExitCode;
+
+use bevy::prelude::*;
+use voxel::block::BlockRegistry;
+use voxel::export::{export_meshes, ExportMesh};
+use voxel::geometry::mesher::ChunkMesher;
+use voxel::world::chunkmap::ChunkMap;
+use voxel::world::generation::WorldGenerator;
+use voxel::world::lighting::update_light;
+use voxel::world::region::WorldStorage;
+
+struct Options {
+  output: PathBuf,
+  center: IVec3,
+  radius: i32,
+  vertical_radius: i32,
+  world: Option<PathBuf>,
+  mesher: ChunkMesher,
+}
+
+fn number(args: &mut impl Iterator<Item = String>, name: &str) -> Result<i32, String> {
+  let value = args.next().ok_or(format!("{name} needs a value"))?;
+  value
+    .parse()
+    .map_err(|_| format!("{name}: {value} is not a number"))
+}
+
+fn parse_options(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+  let mut output = None;
+  let mut options = Options {
+    output: PathBuf::new(),
+    center: IVec3::ZERO,
+    radius: 2,
+    vertical_radius: 1,
+    world: None,
+    mesher: ChunkMesher::default(),
+  };
+
+  while let Some(arg) = args.next() {
+    match arg.as_str() {
+      "--center" => {
+        let [x, y, z] = [(); 3].map(|_| number(&mut args, "--center"));
+        options.center = IVec3::new(x?, y?, z?);
+      }
+      "--radius" => options.radius = number(&mut args, "--radius")?.max(0),
+      "--vertical-radius" => {
+        options.vertical_radius = number(&mut args, "--vertical-radius")?.max(0)
+      }
+      "--world" => options.world = Some(args.next().ok_or("--world needs a directory")?.into()),
+      "--mesher" => {
+        let name = args.next().ok_or("--mesher needs a name")?;
+        let meshers = ChunkMesher::all();
+        let names: Vec<_> = meshers.iter().map(|mesher| mesher.0.name()).collect();
+        options.mesher = meshers
+          .iter()
+          .find(|mesher| mesher.0.name() == name)
+          .cloned()
+          .ok_or(format!(
+            "unknown mesher {name}, the meshers are: {}",
+            names.join(", ")
+          ))?;
+      }
+      _ if arg.starts_with("--") => return Err(format!("unknown option {arg}")),
+      _ if output.is_none() => output = Some(PathBuf::from(arg)),
+      _ => return Err(format!("unexpected argument {arg}")),
+    }
+  }
+
+  options.output = output.ok_or("missing the file to export to")?;
+  Ok(options)
+}
+
+fn main() -> ExitCode {
+  let options = match parse_options(env::args().skip(1)) {
+    Ok(options) => options,
+    Err(error) => {
+      eprintln!("{error}");
+      eprintln!(
+        "usage: export <file.obj|file.glb> [--center x y z] [--radius n] [--vertical-radius n] [--world directory] [--mesher name]"
+      );
+      return ExitCode::FAILURE;
+    }
+  };
+
+  let registry = BlockRegistry::default();
+  let generator = WorldGenerator::default();
+  let storage = options.world.map(WorldStorage::new);
+  let (radius, vertical_radius) = (options.radius, options.vertical_radius);
+
+  // the chunks around the exported ones are loaded too, so that the faces and the light at the borders are right
+  let mut map = ChunkMap::new();
+  for x in -radius - 1..=radius + 1 {
+    for y in -vertical_radius - 1..=vertical_radius + 1 {
+      for z in -radius - 1..=radius + 1 {
+        let coord = options.center + IVec3::new(x, y, z);
+        let saved = match &storage {
+          Some(storage) => match storage.load_chunk(coord) {
+            Ok(saved) => saved,
+            Err(error) => {
+              eprintln!("failed to load the chunk {coord}: {error}");
+              return ExitCode::FAILURE;
+            }
+          },
+          None => None,
+        };
+        map.insert(coord, saved.unwrap_or_else(|| generator.generate(coord)));
+      }
+    }
+  }
+  update_light(&mut map, &registry);
+
+  let mut meshes = Vec::new();
+  for x in -radius..=radius {
+    for y in -vertical_radius..=vertical_radius {
+      for z in -radius..=radius {
+        let coord = options.center + IVec3::new(x, y, z);
+        let Some(chunk) = map.padded_chunk(coord) else {
+          continue;
+        };
+
+        let chunk_meshes = options.mesher.0.mesh(&chunk, &registry);
+        let transform = Transform::from_translation(ChunkMap::chunk_origin(coord).as_vec3());
+        for (layer, mesh) in [
+          ("opaque", chunk_meshes.opaque),
+          ("transparent", chunk_meshes.transparent),
+        ] {
+          meshes.push(ExportMesh {
+            name: format!("chunk_{}_{}_{}_{layer}", coord.x, coord.y, coord.z),
+            mesh,
+            transform,
+          });
+        }
+      }
+    }
+  }
+
+  if let Err(error) = export_meshes(&options.output, &meshes, &registry) {
+    eprintln!("failed to export to {}: {error}", options.output.display());
+    return ExitCode::FAILURE;
+  }
+
+  println!(
+    "exported {} chunks with the {} mesher to {}",
+    meshes.len() / 2,
+    options.mesher.0.name(),
+    options.output.display()
+  );
+  ExitCode::SUCCESS
+}
@@ -0,0 +1,342 @@
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+/**
+ * The id of a block type, as stored in the voxels of a chunk
+ */
+pub type BlockId = u8;
+
+/**
+ * The ids of the blocks registered by `BlockRegistry::default`
+ */
+pub mod blocks {
+  use super::BlockId;
+
+  pub const AIR: BlockId = 0;
+  pub const STONE: BlockId = 1;
+  pub const DIRT: BlockId = 2;
+  pub const GRASS: BlockId = 3;
+  pub const GLASS: BlockId = 4;
+  pub const SAND: BlockId = 5;
+  pub const LAMP: BlockId = 6;
+  pub const WATER: BlockId = 7;
+  pub const LEAVES: BlockId = 8;
+}
+
+/**
+ * The mesh of a chunk a block is rendered in
+ * `Opaque`: rendered without blending, texels with a low alpha are discarded (so cutout blocks go here too)
+ * `Transparent`: rendered with alpha blending, after every opaque mesh
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum RenderLayer {
+  Opaque,
+  Transparent,
+}
+
+/**
+ * The tiles of the texture atlas used by each face of a block (see `BlockAtlas`)
+ * indexed by direction (0 = right, 1 = left, 2 = up, 3 = down, 4 = front, 5 = back)
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub struct BlockTextures(pub [u32; 6]);
+
+impl BlockTextures {
+  /**
+   * Uses the same tile on every face
+   */
+  pub fn all(tile: u32) -> Self {
+    Self([tile; 6])
+  }
+
+  /**
+   * Uses a tile for the top face, one for the bottom face and one for the 4 sides
+   */
+  pub fn top_side_bottom(top: u32, side: u32, bottom: u32) -> Self {
+    Self([side, side, top, bottom, side, side])
+  }
+
+  #[inline]
+  pub fn tile(&self, direction: usize) -> u32 {
+    self.0[direction]
+  }
+}
+
+/**
+ * Describes how a block type behaves
+ * `name`: a unique, human readable name
+ * `solid`: wether the block occupies space (things can't move through it)
+ * `opaque`: wether the block completely hides the faces of the voxels next to it
+ * `transparent`: wether the block is see-through but still rendered (e.g. glass)
+ * `cutout`: wether the texels of a transparent block are either fully opaque or fully see-through (e.g. leaves),
+ *   cutout blocks don't need blending so they are rendered with the opaque blocks
+ * `color`: the average color of the block, used to generate its textures and by everything that can't show textures
+ * `textures`: the tiles of the texture atlas used by each face
+ * `emissive`: the amount of light emitted by the block (0 = none, 15 = max)
+ * `smooth`: wether the block is meshed as a smooth surface instead of cubes (see `surface_nets`), only opaque blocks can be smooth
+ */
+#[derive(Clone, Debug)]
+pub struct BlockProperties {
+  pub name: String,
+  pub solid: bool,
+  pub opaque: bool,
+  pub transparent: bool,
+  pub cutout: bool,
+  pub color: Color,
+  pub textures: BlockTextures,
+  pub emissive: u8,
+  pub smooth: bool,
+}
+
+impl BlockProperties {
+  /**
+   * Returns a solid, opaque block with the given name and color
+   */
+  pub fn opaque(name: &str, color: Color) -> Self {
+    Self {
+      name: name.to_string(),
+      solid: true,
+      opaque: true,
+      transparent: false,
+      cutout: false,
+      color,
+      textures: BlockTextures::all(0),
+      emissive: 0,
+      smooth: false,
+    }
+  }
+
+  /**
+   * Returns a solid, see-through block with the given name and color
+   */
+  pub fn transparent(name: &str, color: Color) -> Self {
+    Self {
+      name: name.to_string(),
+      solid: true,
+      opaque: false,
+      transparent: true,
+      cutout: false,
+      color,
+      textures: BlockTextures::all(0),
+      emissive: 0,
+      smooth: false,
+    }
+  }
+
+  /**
+   * Returns a solid block with see-through holes with the given name and color (see `cutout`)
+   */
+  pub fn cutout(name: &str, color: Color) -> Self {
+    Self {
+      cutout: true,
+      ..Self::transparent(name, color)
+    }
+  }
+
+  /**
+   * Sets the tiles of the texture atlas used by the faces of the block
+   */
+  pub fn with_textures(self, textures: BlockTextures) -> Self {
+    Self { textures, ..self }
+  }
+
+  /**
+   * Makes the block smooth (see `smooth`)
+   */
+  pub fn smoothed(self) -> Self {
+    Self {
+      smooth: true,
+      ..self
+    }
+  }
+
+  /**
+   * Wether the block needs to be rendered
+   */
+  pub fn is_visible(&self) -> bool {
+    self.opaque || self.transparent
+  }
+
+  /**
+   * Wether the block is meshed as a smooth surface (see `smooth`)
+   */
+  pub fn is_smooth(&self) -> bool {
+    self.smooth && self.opaque
+  }
+
+  /**
+   * Wether the block hides every face touching it, smooth blocks are opaque but their rounded surface doesn't cover whole faces
+   */
+  pub fn hides_faces(&self) -> bool {
+    self.opaque && !self.is_smooth()
+  }
+
+  /**
+   * Wether the block has faces that need to be meshed, smooth blocks are part of a surface instead
+   */
+  pub fn has_faces(&self) -> bool {
+    self.is_visible() && !self.is_smooth()
+  }
+
+  /**
+   * The mesh the faces of the block are put in
+   */
+  pub fn layer(&self) -> RenderLayer {
+    if self.transparent && !self.cutout {
+      RenderLayer::Transparent
+    } else {
+      RenderLayer::Opaque
+    }
+  }
+}
+
+/**
+ * Maps block ids to their properties
+ * the block with id 0 is always air
+ */
+#[derive(Resource, Clone, Debug)]
+pub struct BlockRegistry {
+  blocks: Vec<BlockProperties>,
+  names: HashMap<String, BlockId>,
+}
+
+impl BlockRegistry {
+  /**
+   * Returns a registry containing only air
+   */
+  pub fn new() -> Self {
+    let mut registry = Self {
+      blocks: Vec::new(),
+      names: HashMap::default(),
+    };
+
+    registry.register(BlockProperties {
+      name: "air".to_string(),
+      solid: false,
+      opaque: false,
+      transparent: false,
+      cutout: false,
+      color: Color::NONE,
+      textures: BlockTextures::all(0),
+      emissive: 0,
+      smooth: false,
+    });
+
+    registry
+  }
+
+  /**
+   * Adds a new block type to the registry and returns its id
+   * panics if the name is already taken or if there are no more ids available
+   */
+  pub fn register(&mut self, properties: BlockProperties) -> BlockId {
+    assert!(
+      !self.names.contains_key(&properties.name),
+      "block {:?} is already registered",
+      properties.name
+    );
+    let id = BlockId::try_from(self.blocks.len()).expect("too many block types registered");
+
+    self.names.insert(properties.name.clone(), id);
+    self.blocks.push(properties);
+
+    id
+  }
+
+  /**
+   * Returns the properties of a block
+   * panics if the id was never registered
+   */
+  #[inline]
+  pub fn get(&self, id: BlockId) -> &BlockProperties {
+    &self.blocks[id as usize]
+  }
+
+  /**
+   * Returns the properties of a block to change them, the chunks using it have to be remeshed to see the changes
+   * panics if the id was never registered
+   */
+  pub fn get_mut(&mut self, id: BlockId) -> &mut BlockProperties {
+    &mut self.blocks[id as usize]
+  }
+
+  /**
+   * Returns wether the face of `block` touching `neighbour` is hidden
+   * opaque neighbours hide every face, transparent (and cutout) neighbours only hide faces of the same block type
+   * (so there are no faces between two glass blocks, but the faces of stone touching glass are kept)
+   * smooth neighbours don't hide faces, so that blocky faces close the gaps left where the smooth surface is rounded
+   */
+  #[inline]
+  pub fn face_hidden(&self, block: BlockId, neighbour: BlockId) -> bool {
+    let properties = self.get(neighbour);
+    properties.hides_faces() || (properties.transparent && block == neighbour)
+  }
+
+  /**
+   * Returns the id of the block with the given name
+   */
+  pub fn id(&self, name: &str) -> Option<BlockId> {
+    self.names.get(name).copied()
+  }
+
+  pub fn len(&self) -> usize {
+    self.blocks.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.blocks.is_empty()
+  }
+
+  /**
+   * Iterates over every registered block, yields `(id, properties)`
+   */
+  pub fn iter(&self) -> impl Iterator<Item = (BlockId, &BlockProperties)> {
+    self.blocks.iter().enumerate().map(|(id, block)| (id as BlockId, block))
+  }
+}
+
+impl Default for BlockRegistry {
+  /**
+   * Returns a registry with the default blocks (see the `blocks` module)
+   * each block gets its own tiles of the texture atlas, grass uses the dirt tile for its bottom face
+   */
+  fn default() -> Self {
+    let mut registry = Self::new();
+
+    registry.register(
+      BlockProperties::opaque("stone", Color::rgb(0.5, 0.5, 0.5))
+        .with_textures(BlockTextures::all(0)),
+    );
+    registry.register(
+      BlockProperties::opaque("dirt", Color::rgb(0.45, 0.3, 0.2))
+        .with_textures(BlockTextures::all(1)),
+    );
+    registry.register(
+      BlockProperties::opaque("grass", Color::rgb(0.3, 0.6, 0.25))
+        .with_textures(BlockTextures::top_side_bottom(2, 3, 1)),
+    );
+    registry.register(
+      BlockProperties::transparent("glass", Color::rgba(0.75, 0.9, 1.0, 0.3))
+        .with_textures(BlockTextures::all(4)),
+    );
+    registry.register(
+      BlockProperties::opaque("sand", Color::rgb(0.85, 0.8, 0.55))
+        .with_textures(BlockTextures::all(5)),
+    );
+    registry.register(BlockProperties {
+      emissive: 15,
+      ..BlockProperties::opaque("lamp", Color::rgb(1.0, 0.9, 0.6)).with_textures(BlockTextures::all(6))
+    });
+    registry.register(BlockProperties {
+      solid: false,
+      ..BlockProperties::transparent("water", Color::rgba(0.2, 0.4, 0.8, 0.6))
+        .with_textures(BlockTextures::all(7))
+    });
+    registry.register(
+      BlockProperties::cutout("leaves", Color::rgb(0.2, 0.45, 0.15))
+        .with_textures(BlockTextures::all(8)),
+    );
+
+    registry
+  }
+}
@@ -0,0 +1,287 @@
+use std::io::{self, Write};
+
+use bevy::prelude::*;
+use serde_json::{json, Value};
+
+use super::{material_name, ExportMesh, MeshData};
+use crate::block::{BlockId, BlockRegistry, RenderLayer};
+
+const MAGIC: &[u8; 4] = b"glTF";
+const VERSION: u32 = 2;
+const JSON_CHUNK: &[u8; 4] = b"JSON";
+const BIN_CHUNK: &[u8; 4] = b"BIN\0";
+
+// the values glTF uses for the types of its buffer views and accessors
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+
+/**
+ * The binary buffer of a glTF file, along with the buffer views and the accessors describing its content
+ */
+#[derive(Default)]
+struct Buffer {
+  data: Vec<u8>,
+  views: Vec<Value>,
+  accessors: Vec<Value>,
+}
+
+impl Buffer {
+  /**
+   * Adds an accessor for a list of values to the end of the buffer, and returns the index of the accessor
+   * `values`: the values, as little endian bytes
+   * `count`: the amount of values
+   * `accessor`: the fields of the accessor other than its view and its count (e.g. its type)
+   * `target`: wether the values are vertex attributes or indices
+   */
+  fn push(&mut self, values: Vec<u8>, count: usize, mut accessor: Value, target: u32) -> usize {
+    self.views.push(json!({
+      "buffer": 0,
+      "byteOffset": self.data.len(),
+      "byteLength": values.len(),
+      "target": target,
+    }));
+    self.data.extend(values);
+
+    accessor["bufferView"] = json!(self.views.len() - 1);
+    accessor["count"] = json!(count);
+    self.accessors.push(accessor);
+    self.accessors.len() - 1
+  }
+
+  fn push_floats<const N: usize>(&mut self, values: &[[f32; N]], kind: &str) -> usize {
+    let bytes = values
+      .iter()
+      .flatten()
+      .flat_map(|value| value.to_le_bytes())
+      .collect();
+    let accessor = json!({ "componentType": FLOAT, "type": kind });
+    self.push(bytes, values.len(), accessor, ARRAY_BUFFER)
+  }
+}
+
+/**
+ * Writes meshes to a binary glTF 2.0 file
+ * every mesh is a node of the scene with the transform of the mesh, and a primitive per block
+ * the materials are white so that the colors of the vertices (`COLOR_0`), which already contain the color of the blocks, show as they are
+ * the uvs are flipped vertically, textures start from the top in glTF
+ * `meshes`: the meshes to export, empty ones are skipped
+ * `registry`: the registry used to name the materials and know which ones are transparent
+ * `writer`: where the file is written
+ */
+pub fn write_glb(
+  meshes: &[ExportMesh],
+  registry: &BlockRegistry,
+  writer: &mut impl Write,
+) -> io::Result<()> {
+  let mut buffer = Buffer::default();
+  let mut nodes = Vec::new();
+  let mut gltf_meshes = Vec::new();
+  let mut materials: Vec<BlockId> = Vec::new();
+
+  for export in meshes {
+    let Some(data) = MeshData::new(&export.mesh)? else {
+      continue;
+    };
+
+    // the bounds of the positions are required by the format
+    let (min, max) = data.positions.iter().fold(
+      (Vec3::splat(f32::INFINITY), Vec3::splat(f32::NEG_INFINITY)),
+      |(min, max), position| (min.min((*position).into()), max.max((*position).into())),
+    );
+
+    let position = buffer.push_floats(&data.positions, "VEC3");
+    buffer.accessors[position]["min"] = json!(min.to_array());
+    buffer.accessors[position]["max"] = json!(max.to_array());
+    let normal = buffer.push_floats(&data.normals, "VEC3");
+    let uvs: Vec<[f32; 2]> = data.uvs.iter().map(|[u, v]| [*u, 1.0 - v]).collect();
+    let uv = buffer.push_floats(&uvs, "VEC2");
+    let color = buffer.push_floats(&data.colors, "VEC4");
+
+    let primitives: Vec<Value> = data
+      .groups
+      .iter()
+      .map(|(block, triangles)| {
+        let material = materials
+          .iter()
+          .position(|material| material == block)
+          .unwrap_or_else(|| {
+            materials.push(*block);
+            materials.len() - 1
+          });
+
+        let bytes = triangles
+          .iter()
+          .flat_map(|index| index.to_le_bytes())
+          .collect();
+        let accessor = json!({ "componentType": UNSIGNED_INT, "type": "SCALAR" });
+        let indices = buffer.push(bytes, triangles.len(), accessor, ELEMENT_ARRAY_BUFFER);
+
+        json!({
+          "attributes": {
+            "POSITION": position,
+            "NORMAL": normal,
+            "TEXCOORD_0": uv,
+            "COLOR_0": color,
+          },
+          "indices": indices,
+          "material": material,
+        })
+      })
+      .collect();
+
+    gltf_meshes.push(json!({ "name": export.name, "primitives": primitives }));
+
+    let transform = export.transform;
+    nodes.push(json!({
+      "name": export.name,
+      "mesh": gltf_meshes.len() - 1,
+      "translation": transform.translation.to_array(),
+      "rotation": transform.rotation.to_array(),
+      "scale": transform.scale.to_array(),
+    }));
+  }
+
+  let materials: Vec<Value> = materials
+    .iter()
+    .map(|block| {
+      let properties = registry.get(*block);
+      let transparent = properties.layer() == RenderLayer::Transparent;
+      let alpha = if transparent {
+        properties.color.a()
+      } else {
+        1.0
+      };
+
+      json!({
+        "name": material_name(*block, registry),
+        "pbrMetallicRoughness": {
+          "baseColorFactor": [1.0, 1.0, 1.0, alpha],
+          "metallicFactor": 0.0,
+          "roughnessFactor": 1.0,
+        },
+        "alphaMode": if transparent { "BLEND" } else { "OPAQUE" },
+      })
+    })
+    .collect();
+
+  // the chunks of the file have to be aligned to 4 bytes
+  let mut bin = buffer.data;
+  bin.resize(bin.len().next_multiple_of(4), 0);
+
+  let mut document = json!({
+    "asset": { "version": "2.0", "generator": "voxel" },
+    "scene": 0,
+    "scenes": [{ "nodes": (0..nodes.len()).collect::<Vec<_>>() }],
+    "nodes": nodes,
+    "meshes": gltf_meshes,
+    "materials": materials,
+    "accessors": buffer.accessors,
+    "bufferViews": buffer.views,
+  });
+  if !bin.is_empty() {
+    document["buffers"] = json!([{ "byteLength": bin.len() }]);
+  }
+
+  let mut json = serde_json::to_vec(&document)?;
+  json.resize(json.len().next_multiple_of(4), b' ');
+
+  let chunks = [(JSON_CHUNK, json), (BIN_CHUNK, bin)];
+  let chunks: Vec<_> = chunks
+    .into_iter()
+    .filter(|(_, data)| !data.is_empty())
+    .collect();
+  let length = 12 + chunks.iter().map(|(_, data)| 8 + data.len()).sum::<usize>();
+
+  writer.write_all(MAGIC)?;
+  writer.write_all(&VERSION.to_le_bytes())?;
+  writer.write_all(&(length as u32).to_le_bytes())?;
+  for (kind, data) in chunks {
+    writer.write_all(&(data.len() as u32).to_le_bytes())?;
+    writer.write_all(kind)?;
+    writer.write_all(&data)?;
+  }
+
+  Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::block::blocks;
+  use crate::geometry::chunk::Chunk;
+  use crate::geometry::mesher::{GreedyMesher, Mesher};
+  use crate::geometry::padded::PaddedChunk;
+
+  fn read_u32(bytes: &[u8], offset: usize) -> usize {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap()) as usize
+  }
+
+  #[test]
+  fn writes_a_valid_binary_file() {
+    let registry = BlockRegistry::default();
+    let mut chunk = Chunk::new(4);
+    chunk.set(0, 0, 0, blocks::STONE);
+    chunk.set(2, 0, 0, blocks::GLASS);
+
+    let meshes = GreedyMesher.mesh(&PaddedChunk::from_chunk(&chunk), &registry);
+    let exports = [
+      ExportMesh {
+        name: "opaque".to_string(),
+        mesh: meshes.opaque,
+        transform: Transform::from_xyz(32.0, 0.0, 0.0),
+      },
+      ExportMesh {
+        name: "transparent".to_string(),
+        mesh: meshes.transparent,
+        transform: Transform::from_xyz(32.0, 0.0, 0.0),
+      },
+    ];
+
+    let mut glb = Vec::new();
+    write_glb(&exports, &registry, &mut glb).unwrap();
+
+    assert_eq!(&glb[0..4], MAGIC);
+    assert_eq!(read_u32(&glb, 8), glb.len());
+
+    let json_length = read_u32(&glb, 12);
+    assert_eq!(&glb[16..20], JSON_CHUNK);
+    assert_eq!(json_length % 4, 0);
+    let document: Value = serde_json::from_slice(&glb[20..20 + json_length]).unwrap();
+
+    let bin_length = read_u32(&glb, 20 + json_length);
+    assert_eq!(&glb[24 + json_length..28 + json_length], BIN_CHUNK);
+    assert_eq!(glb.len(), 28 + json_length + bin_length);
+    assert_eq!(document["buffers"][0]["byteLength"], bin_length);
+
+    assert_eq!(document["nodes"].as_array().unwrap().len(), 2);
+    assert_eq!(document["nodes"][0]["translation"], json!([32.0, 0.0, 0.0]));
+    assert_eq!(document["materials"][0]["name"], "stone");
+    assert_eq!(document["materials"][1]["alphaMode"], "BLEND");
+
+    // every view fits in the buffer, and every accessor has the right amount of bytes
+    for accessor in document["accessors"].as_array().unwrap() {
+      let view = &document["bufferViews"][accessor["bufferView"].as_u64().unwrap() as usize];
+      let end = view["byteOffset"].as_u64().unwrap() + view["byteLength"].as_u64().unwrap();
+      assert!(end as usize <= bin_length);
+
+      let components = match accessor["type"].as_str().unwrap() {
+        "SCALAR" => 1,
+        "VEC2" => 2,
+        "VEC3" => 3,
+        _ => 4,
+      };
+      assert_eq!(
+        view["byteLength"].as_u64().unwrap(),
+        accessor["count"].as_u64().unwrap() * components * 4
+      );
+    }
+
+    // a cube: 6 quads of 2 triangles
+    let indices = document["meshes"][0]["primitives"][0]["indices"]
+      .as_u64()
+      .unwrap();
+    assert_eq!(document["accessors"][indices as usize]["count"], 36);
+  }
+}
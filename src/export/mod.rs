@@ -0,0 +1,154 @@
+/*!
+ * Exports chunk meshes to files that other tools (e.g. Blender) can open
+ *
+ * two formats are supported, picked from the extension of the file:
+ * - `.obj`: Wavefront OBJ, with the materials in a `.mtl` file next to it
+ * - `.glb`: binary glTF 2.0
+ *
+ * the triangles of every mesh are grouped by block, each block gets its own material named after it
+ */
+
+pub mod gltf;
+pub mod obj;
+
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use bevy::prelude::*;
+use bevy::render::mesh::VertexAttributeValues;
+
+use crate::block::{BlockId, BlockRegistry};
+use crate::geometry::quad::ATTRIBUTE_BLOCK_ID;
+
+/**
+ * A mesh to export, usually the opaque or the transparent mesh of a chunk
+ * `name`: the name of the object in the exported file
+ * `mesh`: a mesh made by a `Mesher`, it needs positions, normals, uvs, colors and block ids (see `QuadMeshBuilder`)
+ * `transform`: where the mesh is in the world, usually at the origin of its chunk and scaled by its level of detail
+ */
+#[derive(Clone, Debug)]
+pub struct ExportMesh {
+  pub name: String,
+  pub mesh: Mesh,
+  pub transform: Transform,
+}
+
+/**
+ * The attributes of a mesh, read back from a `Mesh`
+ * `groups`: the triangles (3 indices each) of each block, in the order the blocks first appear
+ */
+struct MeshData {
+  positions: Vec<[f32; 3]>,
+  normals: Vec<[f32; 3]>,
+  uvs: Vec<[f32; 2]>,
+  colors: Vec<[f32; 4]>,
+  groups: Vec<(BlockId, Vec<u32>)>,
+}
+
+impl MeshData {
+  /**
+   * Reads the attributes of a mesh, returns `None` if the mesh has no triangles
+   * the triangles get the block of their first vertex
+   */
+  fn new(mesh: &Mesh) -> io::Result<Option<Self>> {
+    let Some(indices) = mesh.indices() else {
+      return Ok(None);
+    };
+    if indices.is_empty() {
+      return Ok(None);
+    }
+
+    let missing = |name: &str| invalid_mesh(format!("the mesh has no {name}"));
+    let positions = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+      Some(VertexAttributeValues::Float32x3(positions)) => positions.clone(),
+      _ => return Err(missing("positions")),
+    };
+    let normals = match mesh.attribute(Mesh::ATTRIBUTE_NORMAL) {
+      Some(VertexAttributeValues::Float32x3(normals)) => normals.clone(),
+      _ => return Err(missing("normals")),
+    };
+    let uvs = match mesh.attribute(Mesh::ATTRIBUTE_UV_0) {
+      Some(VertexAttributeValues::Float32x2(uvs)) => uvs.clone(),
+      _ => return Err(missing("uvs")),
+    };
+    let colors = match mesh.attribute(Mesh::ATTRIBUTE_COLOR) {
+      Some(VertexAttributeValues::Float32x4(colors)) => colors.clone(),
+      _ => return Err(missing("colors")),
+    };
+    let blocks = match mesh.attribute(ATTRIBUTE_BLOCK_ID) {
+      Some(VertexAttributeValues::Uint32(blocks)) => blocks,
+      _ => return Err(missing("block ids")),
+    };
+
+    let indices: Vec<u32> = indices.iter().map(|index| index as u32).collect();
+    let mut groups: Vec<(BlockId, Vec<u32>)> = Vec::new();
+    for triangle in indices.chunks_exact(3) {
+      let block = blocks[triangle[0] as usize] as BlockId;
+      match groups.iter_mut().find(|(group, _)| *group == block) {
+        Some((_, group)) => group.extend(triangle),
+        None => groups.push((block, triangle.to_vec())),
+      }
+    }
+
+    Ok(Some(Self {
+      positions,
+      normals,
+      uvs,
+      colors,
+      groups,
+    }))
+  }
+}
+
+fn invalid_mesh(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidInput, message)
+}
+
+/**
+ * Returns the name of the material of a block, the name of the block without the characters other tools may not like
+ */
+fn material_name(block: BlockId, registry: &BlockRegistry) -> String {
+  registry
+    .get(block)
+    .name
+    .chars()
+    .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+    .collect()
+}
+
+/**
+ * Writes meshes to a file, in the format given by its extension (`.obj` or `.glb`, see `obj` and `gltf`)
+ * the directory of the file is created if needed, OBJ files get their materials written to a `.mtl` file next to them
+ * `path`: the file to write
+ * `meshes`: the meshes to export, empty ones are skipped
+ * `registry`: the registry used to name and color the materials
+ */
+pub fn export_meshes(path: &Path, meshes: &[ExportMesh], registry: &BlockRegistry) -> io::Result<()> {
+  if let Some(directory) = path.parent().filter(|directory| !directory.as_os_str().is_empty()) {
+    fs::create_dir_all(directory)?;
+  }
+
+  let extension = path.extension().and_then(|extension| extension.to_str());
+  match extension.map(|extension| extension.to_ascii_lowercase()).as_deref() {
+    Some("obj") => {
+      let mtl_path = path.with_extension("mtl");
+      let mtl_name = mtl_path.file_name().and_then(|name| name.to_str()).unwrap_or("materials.mtl");
+
+      let mut obj = BufWriter::new(File::create(path)?);
+      let mut mtl = BufWriter::new(File::create(&mtl_path)?);
+      obj::write_obj(meshes, registry, &mut obj, &mut mtl, mtl_name)?;
+      mtl.into_inner().map_err(|error| error.into_error())?.sync_all()?;
+      obj.into_inner().map_err(|error| error.into_error())?.sync_all()
+    }
+    Some("glb") => {
+      let mut glb = BufWriter::new(File::create(path)?);
+      gltf::write_glb(meshes, registry, &mut glb)?;
+      glb.into_inner().map_err(|error| error.into_error())?.sync_all()
+    }
+    _ => Err(invalid_mesh(format!(
+      "can't export to {}, the file must end with .obj or .glb",
+      path.display()
+    ))),
+  }
+}
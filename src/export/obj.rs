@@ -0,0 +1,129 @@
+use std::io::{self, Write};
+
+use bevy::prelude::*;
+
+use super::{material_name, ExportMesh, MeshData};
+use crate::block::{BlockId, BlockRegistry};
+
+/**
+ * Writes meshes to a Wavefront OBJ file and its materials to a MTL file
+ * every mesh is an object, its vertices are moved by its transform, and its triangles are grouped by block with `usemtl`
+ * the colors of the vertices are written after their positions (`v x y z r g b`), an extension most tools understand
+ * `meshes`: the meshes to export, empty ones are skipped
+ * `registry`: the registry used to name and color the materials
+ * `obj`, `mtl`: where the OBJ file and the MTL file are written
+ * `mtl_name`: the path of the MTL file relative to the OBJ file
+ */
+pub fn write_obj(
+  meshes: &[ExportMesh],
+  registry: &BlockRegistry,
+  obj: &mut impl Write,
+  mtl: &mut impl Write,
+  mtl_name: &str,
+) -> io::Result<()> {
+  writeln!(obj, "# {} chunk meshes", meshes.len())?;
+  writeln!(obj, "mtllib {mtl_name}")?;
+
+  // the indices of the vertices of a file start from 1 and keep going across objects
+  let mut first_vertex = 1;
+  let mut materials: Vec<BlockId> = Vec::new();
+
+  for export in meshes {
+    let Some(data) = MeshData::new(&export.mesh)? else {
+      continue;
+    };
+    let transform = export.transform;
+
+    writeln!(obj, "o {}", export.name)?;
+    for (position, color) in data.positions.iter().zip(&data.colors) {
+      let p = transform.transform_point((*position).into());
+      writeln!(
+        obj,
+        "v {} {} {} {} {} {}",
+        p.x, p.y, p.z, color[0], color[1], color[2]
+      )?;
+    }
+    for uv in &data.uvs {
+      writeln!(obj, "vt {} {}", uv[0], uv[1])?;
+    }
+    for normal in &data.normals {
+      let n = (transform.rotation * Vec3::from(*normal)).normalize_or_zero();
+      writeln!(obj, "vn {} {} {}", n.x, n.y, n.z)?;
+    }
+
+    for (block, triangles) in &data.groups {
+      if !materials.contains(block) {
+        materials.push(*block);
+      }
+
+      writeln!(obj, "usemtl {}", material_name(*block, registry))?;
+      for triangle in triangles.chunks_exact(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| triangle[i] + first_vertex);
+        writeln!(obj, "f {a}/{a}/{a} {b}/{b}/{b} {c}/{c}/{c}")?;
+      }
+    }
+
+    first_vertex += data.positions.len() as u32;
+  }
+
+  for block in materials {
+    let color = registry.get(block).color.as_rgba_f32();
+    writeln!(mtl, "newmtl {}", material_name(block, registry))?;
+    writeln!(mtl, "Kd {} {} {}", color[0], color[1], color[2])?;
+    writeln!(mtl, "d {}", color[3])?;
+    writeln!(mtl)?;
+  }
+
+  // buffered writers only report the errors of their last writes when they are flushed
+  obj.flush()?;
+  mtl.flush()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::block::blocks;
+  use crate::geometry::chunk::Chunk;
+  use crate::geometry::mesher::{GreedyMesher, Mesher};
+  use crate::geometry::padded::PaddedChunk;
+
+  #[test]
+  fn writes_one_material_group_per_block() {
+    let registry = BlockRegistry::default();
+    let mut chunk = Chunk::new(4);
+    chunk.set(0, 0, 0, blocks::STONE);
+    chunk.set(2, 0, 0, blocks::DIRT);
+    chunk.set(2, 1, 0, blocks::DIRT);
+
+    let meshes = GreedyMesher.mesh(&PaddedChunk::from_chunk(&chunk), &registry);
+    let exports = [
+      ExportMesh {
+        name: "opaque".to_string(),
+        mesh: meshes.opaque,
+        transform: Transform::from_xyz(32.0, 0.0, 0.0),
+      },
+      ExportMesh {
+        name: "transparent".to_string(),
+        mesh: meshes.transparent,
+        transform: Transform::IDENTITY,
+      },
+    ];
+
+    let (mut obj, mut mtl) = (Vec::new(), Vec::new());
+    write_obj(&exports, &registry, &mut obj, &mut mtl, "chunks.mtl").unwrap();
+    let (obj, mtl) = (String::from_utf8(obj).unwrap(), String::from_utf8(mtl).unwrap());
+    let lines = |prefix: &str| obj.lines().filter(|line| line.starts_with(prefix)).count();
+
+    // the empty transparent mesh is skipped
+    assert_eq!(lines("o "), 1);
+    assert_eq!(lines("usemtl "), 2);
+    // a cube and a 1x2x1 box, 6 quads each
+    assert_eq!(lines("f "), 2 * 6 * 2);
+    assert_eq!(lines("v "), lines("vn "));
+    assert!(obj.lines().any(|line| line == "usemtl stone"));
+    assert!(obj.lines().all(|line| !line.starts_with("v ") || line.starts_with("v 3")));
+
+    assert_eq!(mtl.matches("newmtl ").count(), 2);
+    assert!(mtl.contains("newmtl dirt\nKd 0.45 0.3 0.2\n"));
+  }
+}
@@ -0,0 +1,62 @@
+use bevy::prelude::*;
+
+use super::padded::PaddedChunk;
+use super::quad::{direction_offset, expand_direction};
+use crate::block::BlockRegistry;
+
+/**
+ * How much light reaches a vertex for each ambient occlusion value (0 = fully occluded, 3 = not occluded)
+ */
+pub const AO_CURVE: [f32; 4] = [0.4, 0.6, 0.8, 1.0];
+
+/**
+ * The sign of the offset of each corner of a face along the width and height directions
+ * the corners are in the same order as the vertices of a quad (see `QuadMeshBuilder::push_quad`)
+ */
+const CORNERS: [(i32, i32); 4] = [(-1, -1), (1, -1), (1, 1), (-1, 1)];
+
+/**
+ * Returns the ambient occlusion value of a vertex from the 3 voxels around it
+ * `side1`, `side2`: wether the voxels sharing an edge with the vertex are occluding
+ * `corner`: wether the voxel sharing only the vertex is occluding
+ * returns a value from 0 (fully occluded) to 3 (not occluded)
+ */
+#[inline]
+pub fn vertex_ao(side1: bool, side2: bool, corner: bool) -> u8 {
+  if side1 && side2 {
+    return 0;
+  }
+
+  3 - (side1 as u8 + side2 as u8 + corner as u8)
+}
+
+/**
+ * Computes the ambient occlusion of the 4 corners of a face
+ * looks at the 8 voxels around the voxel in front of the face, only opaque voxels occlude
+ * `face`: the voxel the face belongs to
+ * `direction`: the direction of the face (0 = right, 1 = left, 2 = up, 3 = down, 4 = front, 5 = back)
+ * `chunk`: the chunk the face is in, its border is used for the faces on the edge of the chunk
+ * `registry`: the registry used to know which voxels are opaque
+ */
+pub fn face_ao(
+  face: &(usize, usize, usize),
+  direction: usize,
+  chunk: &PaddedChunk,
+  registry: &BlockRegistry,
+) -> [u8; 4] {
+  let expand = expand_direction(direction);
+  let width = IVec3::new(expand.width[0] as i32, expand.width[1] as i32, expand.width[2] as i32);
+  let height = IVec3::new(expand.height[0] as i32, expand.height[1] as i32, expand.height[2] as i32);
+
+  // the layer of voxels in front of the face
+  let front = IVec3::new(face.0 as i32, face.1 as i32, face.2 as i32) + direction_offset(direction);
+  let occludes = |p: IVec3| registry.get(chunk.get(p.x, p.y, p.z)).opaque;
+
+  CORNERS.map(|(w, h)| {
+    let side1 = occludes(front + width * w);
+    let side2 = occludes(front + height * h);
+    let corner = occludes(front + width * w + height * h);
+
+    vertex_ao(side1, side2, corner)
+  })
+}
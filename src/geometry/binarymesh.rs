@@ -0,0 +1,310 @@
+use super::padded::PaddedChunk;
+use super::quad::{FaceKey, Quad};
+use crate::block::BlockRegistry;
+
+/**
+ * The biggest chunk the binary mesher can handle, every row of a slice has to fit in a `u64`
+ */
+pub const MAX_BINARY_SIZE: usize = 64;
+
+/**
+ * Bitmasks of the voxels of a chunk, one `u64` per column of voxels
+ * the columns are indexed by 2 coordinates (from -1 to size, so the border of the chunk is included)
+ * and hold one bit per voxel along the third coordinate (from 0 to size)
+ * `visible`: the voxels with faces to mesh
+ * `opaque`: the voxels that hide every face touching them
+ * `transparent`: the voxels that hide the faces of the same block touching them
+ */
+struct ColumnMasks {
+  stride: usize,
+  visible: Vec<u64>,
+  opaque: Vec<u64>,
+  transparent: Vec<u64>,
+}
+
+impl ColumnMasks {
+  fn new(size: usize) -> Self {
+    let stride = size + 2;
+    Self {
+      stride,
+      visible: vec![0; stride * stride],
+      opaque: vec![0; stride * stride],
+      transparent: vec![0; stride * stride],
+    }
+  }
+
+  #[inline]
+  fn index(&self, u: i32, v: i32) -> usize {
+    (u + 1) as usize * self.stride + (v + 1) as usize
+  }
+}
+
+/**
+ * How the slices of faces facing a direction are laid out
+ * every slice is a grid of rows, in the same order the greedy mesher finds its faces (see `FaceFinder`)
+ * `axes`: the axis of the normal, of the rows and of the bits of each row (0 = x, 1 = y, 2 = z)
+ * `width_along_row`: wether the width of the quads (see `expand_direction`) runs along the bits of a row or across rows
+ */
+struct SliceLayout {
+  axes: [usize; 3],
+  width_along_row: bool,
+}
+
+fn slice_layout(direction: usize) -> SliceLayout {
+  match direction {
+    // slices along x, rows along y, bits along z
+    0 | 1 => SliceLayout {
+      axes: [0, 1, 2],
+      width_along_row: true,
+    },
+    // slices along y, rows along x, bits along z
+    2 | 3 => SliceLayout {
+      axes: [1, 0, 2],
+      width_along_row: false,
+    },
+    // slices along z, rows along x, bits along y
+    4 | 5 => SliceLayout {
+      axes: [2, 0, 1],
+      width_along_row: false,
+    },
+    _ => panic!("Invalid direction!"),
+  }
+}
+
+/**
+ * Returns a mask with the lowest `count` bits set
+ */
+#[inline]
+fn low_bits(count: u32) -> u64 {
+  if count >= 64 {
+    u64::MAX
+  } else {
+    (1 << count) - 1
+  }
+}
+
+/**
+ * Returns the length of the run of mergeable faces of a row starting at `bit`
+ * `row`: the faces of the row that haven't been meshed yet
+ * `same_key`: the faces of the row that have the same key as the next face in the row
+ */
+#[inline]
+fn run_length(row: u64, same_key: u64, bit: u32) -> u32 {
+  1 + (((row >> 1) & same_key) >> bit).trailing_ones()
+}
+
+/**
+ * Runs the greedy meshing algorithm on a chunk using bitmasks and returns the resulting quads
+ * culling and merging work on whole rows of faces at a time, the quads are exactly the ones of `greedy_quads`, in the same order
+ * panics if the chunk is bigger than `MAX_BINARY_SIZE`
+ * `chunk`: the chunk to run the algorithm on, its border is only used to cull faces
+ * `registry`: the registry used to decide which faces are visible
+ */
+pub fn binary_quads(chunk: &PaddedChunk, registry: &BlockRegistry) -> Vec<Quad> {
+  let size = chunk.size;
+  assert!(size <= MAX_BINARY_SIZE, "chunks bigger than {MAX_BINARY_SIZE} can't be binary meshed");
+  let s = size as i32;
+
+  // columns along z indexed by (x, y), and along y indexed by (x, z)
+  let mut z_columns = ColumnMasks::new(size);
+  let mut y_columns = ColumnMasks::new(size);
+
+  for x in -1..=s {
+    for y in -1..=s {
+      for z in -1..=s {
+        let properties = registry.get(chunk.get(x, y, z));
+
+        for (columns, (u, v), bit) in [(&mut z_columns, (x, y), z), (&mut y_columns, (x, z), y)] {
+          if bit < 0 || bit >= s {
+            continue;
+          }
+
+          let index = columns.index(u, v);
+          columns.visible[index] |= (properties.has_faces() as u64) << bit;
+          columns.opaque[index] |= (properties.hides_faces() as u64) << bit;
+          columns.transparent[index] |= (properties.transparent as u64) << bit;
+        }
+      }
+    }
+  }
+
+  let mut quads = Vec::new();
+
+  // the faces of the current slice that haven't been meshed yet, one row per entry
+  let mut rows = vec![0u64; size];
+  // the key of every face of the current slice
+  let mut keys: Vec<Option<FaceKey>> = vec![None; size * size];
+  // the faces with the same key as the next face in their row, and as the face in the same place in the next row
+  let mut same_next_bit = vec![0u64; size];
+  let mut same_next_row = vec![0u64; size];
+
+  for d in 0..6 {
+    let layout = slice_layout(d);
+    let [normal_axis, row_axis, bit_axis] = layout.axes;
+    let columns = if d < 4 { &z_columns } else { &y_columns };
+    let step = if d % 2 == 0 { 1 } else { -1 };
+
+    // z columns are indexed by (x, y) and y columns by (x, z), the x axis always comes first
+    let column = |slice: i32, row: i32| {
+      if normal_axis == 0 {
+        columns.index(slice, row)
+      } else {
+        columns.index(row, slice)
+      }
+    };
+
+    let position = |slice: usize, row: usize, bit: usize| {
+      let mut position = [0; 3];
+      position[normal_axis] = slice;
+      position[row_axis] = row;
+      position[bit_axis] = bit;
+      (position[0], position[1], position[2])
+    };
+
+    for slice in 0..size {
+      // culling: a face is visible if its voxel is, and the voxel in front of it doesn't hide it
+      for (row, faces) in rows.iter_mut().enumerate() {
+        let here = column(slice as i32, row as i32);
+        let front = column(slice as i32 + step, row as i32);
+
+        *faces = columns.visible[here] & !columns.opaque[front];
+
+        // transparent voxels only hide the faces of the same block
+        let mut same_block = *faces & columns.transparent[front];
+        while same_block != 0 {
+          let bit = same_block.trailing_zeros() as usize;
+          same_block &= same_block - 1;
+
+          let face = position(slice, row, bit);
+          let mut neighbour = [face.0 as i32, face.1 as i32, face.2 as i32];
+          neighbour[normal_axis] += step;
+
+          let block = chunk.get(face.0 as i32, face.1 as i32, face.2 as i32);
+          if block == chunk.get(neighbour[0], neighbour[1], neighbour[2]) {
+            *faces &= !(1 << bit);
+          }
+        }
+
+        let mut remaining = *faces;
+        while remaining != 0 {
+          let bit = remaining.trailing_zeros() as usize;
+          remaining &= remaining - 1;
+
+          let face = position(slice, row, bit);
+          keys[row * size + bit] = Some(FaceKey::new(&face, d, chunk, registry));
+        }
+      }
+
+      for row in 0..size {
+        let key = |bit: usize| keys[row * size + bit];
+
+        same_next_bit[row] = 0;
+        let mut pairs = rows[row] & (rows[row] >> 1);
+        while pairs != 0 {
+          let bit = pairs.trailing_zeros() as usize;
+          pairs &= pairs - 1;
+
+          if key(bit) == key(bit + 1) {
+            same_next_bit[row] |= 1 << bit;
+          }
+        }
+
+        same_next_row[row] = 0;
+        let mut pairs = if row + 1 < size { rows[row] & rows[row + 1] } else { 0 };
+        while pairs != 0 {
+          let bit = pairs.trailing_zeros() as usize;
+          pairs &= pairs - 1;
+
+          if key(bit) == keys[(row + 1) * size + bit] {
+            same_next_row[row] |= 1 << bit;
+          }
+        }
+      }
+
+      // merging: the first face left is expanded in the width direction, then in the height direction
+      for row in 0..size {
+        while rows[row] != 0 {
+          let bit = rows[row].trailing_zeros();
+          let key = keys[row * size + bit as usize].expect("queued faces always have a key");
+
+          let (width, height) = if layout.width_along_row {
+            let width = run_length(rows[row], same_next_bit[row], bit);
+            let mask = low_bits(width) << bit;
+            rows[row] &= !mask;
+
+            // the next rows must have the whole run, with the same key
+            let mut height = 1;
+            while row + height < size && rows[row + height] & same_next_row[row + height - 1] & mask == mask {
+              rows[row + height] &= !mask;
+              height += 1;
+            }
+
+            (width as usize, height)
+          } else {
+            let mut width = 1;
+            while row + width < size && rows[row + width] & same_next_row[row + width - 1] & (1 << bit) != 0 {
+              width += 1;
+            }
+
+            // the quad is as tall as the shortest run of the rows it covers
+            let height = (row..row + width)
+              .map(|r| run_length(rows[r], same_next_bit[r], bit))
+              .min()
+              .unwrap_or(1);
+            let mask = low_bits(height) << bit;
+            for faces in &mut rows[row..row + width] {
+              *faces &= !mask;
+            }
+
+            (width, height as usize)
+          };
+
+          quads.push(Quad {
+            position: position(slice, row, bit as usize),
+            direction: d,
+            width,
+            height,
+            key,
+          });
+        }
+      }
+    }
+  }
+
+  // the greedy mesher finds its quads direction by direction, in the order of the voxels of the chunk
+  quads.sort_unstable_by_key(|quad| (quad.direction, quad.position));
+
+  quads
+}
+
+#[cfg(test)]
+mod tests {
+  use rand::prelude::*;
+
+  use super::*;
+  use crate::block::{blocks, BlockId};
+  use crate::geometry::chunk::Chunk;
+  use crate::geometry::greedymesh::greedy_quads;
+
+  #[test]
+  fn same_quads_as_greedy_mesher() {
+    let registry = BlockRegistry::default();
+    let mut rng = StdRng::seed_from_u64(12);
+    let palettes: [&[BlockId]; 3] = [
+      &[blocks::AIR, blocks::STONE],
+      &[blocks::STONE, blocks::STONE, blocks::DIRT],
+      &[blocks::AIR, blocks::AIR, blocks::STONE, blocks::GLASS, blocks::WATER, blocks::LEAVES],
+    ];
+
+    for size in [1, 3, 16, MAX_BINARY_SIZE] {
+      for palette in palettes {
+        let chunks: Vec<Chunk> = (0..2)
+          .map(|_| Chunk::random_with(&mut rng, size, palette))
+          .collect();
+
+        let padded = PaddedChunk::from_neighbours(&chunks[0], |_| Some(&chunks[1]));
+        assert_eq!(binary_quads(&padded, &registry), greedy_quads(&padded, &registry));
+      }
+    }
+  }
+}
@@ -0,0 +1,434 @@
+use rand::prelude::*;
+
+use super::light::{Light, LightField};
+use crate::block::{blocks, BlockId};
+
+/**
+ * Returns the amount of bits needed to index a palette of `len` blocks
+ * always a power of 2 (or 0 for a single block), so that indices never straddle two words
+ */
+fn palette_bits(len: usize) -> u32 {
+  match len {
+    0 | 1 => 0,
+    2 => 1,
+    3..=4 => 2,
+    5..=16 => 4,
+    _ => 8,
+  }
+}
+
+/**
+ * A cubic chunk of voxels, stored as a palette of the blocks in the chunk and a bit-packed index into it for each voxel
+ * the indices grow from 0 bits (a single block) up to 8 bits as more distinct blocks are put in the chunk
+ * `size`: the length of a side of the chunk (in voxels)
+ * `palette`: the distinct blocks in the chunk (blocks are never removed from the palette)
+ * `bits`: the amount of bits of each index
+ * `indices`: the indices of the voxels, laid out so that `z` is the fastest changing axis (see `Chunk::index`)
+ * `light`: the light of the voxels, filled in by the light engine of the world (see `lighting`)
+ */
+#[derive(Clone, Debug)]
+pub struct Chunk {
+  pub size: usize,
+  palette: Vec<BlockId>,
+  bits: u32,
+  indices: Vec<u64>,
+  light: LightField,
+}
+
+impl Chunk {
+  /**
+   * Returns a chunk filled with air
+   */
+  pub fn new(size: usize) -> Self {
+    Self::filled(size, blocks::AIR)
+  }
+
+  /**
+   * Returns a chunk where every voxel is `block`, no memory is used for the voxels until a different block is put in the chunk
+   */
+  pub fn filled(size: usize, block: BlockId) -> Self {
+    Self {
+      size,
+      palette: vec![block],
+      bits: 0,
+      indices: Vec::new(),
+      light: LightField::default(),
+    }
+  }
+
+  /**
+   * Returns a chunk where every voxel is picked at random from `blocks`
+   */
+  pub fn random(size: usize, blocks: &[BlockId]) -> Self {
+    Self::random_with(&mut thread_rng(), size, blocks)
+  }
+
+  /**
+   * Returns a chunk where every voxel is picked from `blocks` by `rng`, a seeded `rng` always gives the same chunks
+   */
+  pub fn random_with(rng: &mut impl Rng, size: usize, blocks: &[BlockId]) -> Self {
+    let voxels = (0..size * size * size)
+      .map(|_| *blocks.choose(rng).expect("no blocks to pick from"))
+      .collect();
+
+    Self::from_voxels(size, voxels).expect("the amount of voxels matches the size")
+  }
+
+  /**
+   * Returns a chunk made of the given voxels, in the layout of `Chunk::index`
+   * returns `None` if there aren't exactly `size^3` voxels
+   */
+  pub fn from_voxels(size: usize, voxels: Vec<BlockId>) -> Option<Self> {
+    if voxels.len() != size * size * size {
+      return None;
+    }
+
+    let mut palette = Vec::new();
+    for voxel in &voxels {
+      if !palette.contains(voxel) {
+        palette.push(*voxel);
+      }
+    }
+
+    let mut chunk = Self {
+      size,
+      bits: palette_bits(palette.len()),
+      palette,
+      indices: Vec::new(),
+      light: LightField::default(),
+    };
+    chunk.indices = vec![0; chunk.words(chunk.bits)];
+
+    for (i, voxel) in voxels.iter().enumerate() {
+      let entry = chunk
+        .palette
+        .iter()
+        .position(|block| block == voxel)
+        .unwrap_or(0);
+      chunk.set_entry(i, entry);
+    }
+
+    Some(chunk)
+  }
+
+  /**
+   * Returns a copy of the voxels of the chunk, in the layout of `Chunk::index`
+   */
+  pub fn to_voxels(&self) -> Vec<BlockId> {
+    (0..self.volume())
+      .map(|i| self.palette[self.entry(i)])
+      .collect()
+  }
+
+  /**
+   * The distinct blocks that have been put in the chunk
+   * a block in the palette is not necessarily still in the chunk, blocks are never removed
+   */
+  pub fn palette(&self) -> &[BlockId] {
+    &self.palette
+  }
+
+  /**
+   * Returns the block every voxel of the chunk is made of, or `None` if the chunk has different blocks
+   * chunks that became uniform through edits are only detected after `Chunk::compact`
+   */
+  #[inline]
+  pub fn uniform_block(&self) -> Option<BlockId> {
+    (self.bits == 0).then(|| self.palette[0])
+  }
+
+  /**
+   * Removes the blocks that are not in the chunk anymore from the palette, shrinking the indices if possible
+   * a chunk left with a single block stops using memory for its voxels
+   */
+  pub fn compact(&mut self) {
+    if self.bits == 0 {
+      return;
+    }
+
+    let mut used = vec![false; self.palette.len()];
+    for i in 0..self.volume() {
+      used[self.entry(i)] = true;
+    }
+    if used.iter().all(|used| *used) {
+      return;
+    }
+
+    // the new palette entry of every old one
+    let mut remap = vec![0; self.palette.len()];
+    let mut palette = Vec::new();
+    for (entry, block) in self.palette.iter().enumerate() {
+      if used[entry] {
+        remap[entry] = palette.len();
+        palette.push(*block);
+      }
+    }
+
+    let entries: Vec<usize> = (0..self.volume()).map(|i| remap[self.entry(i)]).collect();
+    self.bits = palette_bits(palette.len());
+    self.palette = palette;
+    self.indices = vec![0; self.words(self.bits)];
+    for (i, entry) in entries.into_iter().enumerate() {
+      self.set_entry(i, entry);
+    }
+  }
+
+  /**
+   * Returns the amount of bytes used by the chunk
+   */
+  pub fn memory_usage(&self) -> usize {
+    std::mem::size_of::<Self>()
+      + self.palette.capacity() * std::mem::size_of::<BlockId>()
+      + self.indices.capacity() * std::mem::size_of::<u64>()
+      + self.light.memory_usage()
+  }
+
+  #[inline]
+  fn volume(&self) -> usize {
+    self.size * self.size * self.size
+  }
+
+  /**
+   * Returns the amount of words needed to hold every index with `bits` bits each
+   */
+  fn words(&self, bits: u32) -> usize {
+    if bits == 0 {
+      return 0;
+    }
+
+    self.volume().div_ceil((64 / bits) as usize)
+  }
+
+  /**
+   * Returns the palette index of the voxel at `index` in the buffer
+   */
+  #[inline]
+  fn entry(&self, index: usize) -> usize {
+    if self.bits == 0 {
+      return 0;
+    }
+
+    let per_word = (64 / self.bits) as usize;
+    let shift = (index % per_word) as u32 * self.bits;
+    ((self.indices[index / per_word] >> shift) & ((1 << self.bits) - 1)) as usize
+  }
+
+  #[inline]
+  fn set_entry(&mut self, index: usize, entry: usize) {
+    if self.bits == 0 {
+      return;
+    }
+
+    let per_word = (64 / self.bits) as usize;
+    let shift = (index % per_word) as u32 * self.bits;
+    let word = &mut self.indices[index / per_word];
+    *word = (*word & !(((1 << self.bits) - 1) << shift)) | ((entry as u64) << shift);
+  }
+
+  /**
+   * Rewrites every index with a different amount of bits
+   */
+  fn repack(&mut self, bits: u32) {
+    let entries: Vec<usize> = (0..self.volume()).map(|i| self.entry(i)).collect();
+
+    self.bits = bits;
+    self.indices = vec![0; self.words(bits)];
+    for (i, entry) in entries.into_iter().enumerate() {
+      self.set_entry(i, entry);
+    }
+  }
+
+  /**
+   * Returns the position of a voxel inside the buffer
+   * the layout is `x` major, `z` minor, so walking `z` walks contiguous memory
+   */
+  #[inline]
+  pub fn index(&self, x: usize, y: usize, z: usize) -> usize {
+    debug_assert!(x < self.size && y < self.size && z < self.size);
+    (x * self.size + y) * self.size + z
+  }
+
+  /**
+   * Returns the coordinates of the voxel stored at `index` (the inverse of `Chunk::index`)
+   */
+  #[inline]
+  pub fn position(&self, index: usize) -> (usize, usize, usize) {
+    (
+      index / (self.size * self.size),
+      index / self.size % self.size,
+      index % self.size,
+    )
+  }
+
+  #[inline]
+  pub fn get(&self, x: usize, y: usize, z: usize) -> BlockId {
+    self.palette[self.entry(self.index(x, y, z))]
+  }
+
+  /**
+   * Changes a voxel, blocks that are not in the chunk yet are added to the palette (making the indices bigger if needed)
+   */
+  pub fn set(&mut self, x: usize, y: usize, z: usize, voxel: BlockId) {
+    let index = self.index(x, y, z);
+    if self.palette[self.entry(index)] == voxel {
+      return;
+    }
+
+    let entry = match self.palette.iter().position(|block| *block == voxel) {
+      Some(entry) => entry,
+      None => {
+        self.palette.push(voxel);
+        let bits = palette_bits(self.palette.len());
+        if bits != self.bits {
+          self.repack(bits);
+        }
+        self.palette.len() - 1
+      }
+    };
+
+    self.set_entry(index, entry);
+  }
+
+  #[inline]
+  pub fn light(&self, x: usize, y: usize, z: usize) -> Light {
+    self.light.get(self.index(x, y, z))
+  }
+
+  pub fn set_light(&mut self, x: usize, y: usize, z: usize, light: Light) {
+    let index = self.index(x, y, z);
+    self.light.set(index, self.volume(), light);
+  }
+
+  /**
+   * Gives the same light to every voxel of the chunk
+   */
+  pub fn fill_light(&mut self, light: Light) {
+    self.light.fill(light);
+  }
+
+  /**
+   * Returns the light of every voxel of the chunk, in the layout of `Chunk::index`
+   */
+  pub fn light_field(&self) -> &LightField {
+    &self.light
+  }
+
+  /**
+   * Frees the memory used by the light if every voxel has the same light (see `LightField::compact`)
+   */
+  pub fn compact_light(&mut self) {
+    self.light.compact();
+  }
+
+  /**
+   * Iterates over every voxel of the chunk in memory order
+   * yields `((x, y, z), voxel)`
+   */
+  pub fn iter(&self) -> impl Iterator<Item = ((usize, usize, usize), BlockId)> + '_ {
+    (0..self.volume()).map(|i| (self.position(i), self.palette[self.entry(i)]))
+  }
+
+  pub fn print(&self) {
+    for z in 0..self.size {
+      print!("y|x ");
+      for y in 0..self.size {
+        print!("{} ", y);
+      }
+      println!();
+
+      print!(" +-");
+      for _ in 0..self.size {
+        print!("--");
+      }
+      println!("   z = {}", z);
+
+      for y in 0..self.size {
+        print!("{}|  ", y);
+        for x in 0..self.size {
+          print!("{} ", self.get(x, y, z));
+        }
+        println!();
+      }
+      println!();
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use rand::prelude::*;
+
+  use super::*;
+
+  #[test]
+  fn palette_grows_with_distinct_blocks() {
+    let size = 8;
+    let mut rng = StdRng::seed_from_u64(17);
+    let mut chunk = Chunk::new(size);
+    let mut voxels = vec![blocks::AIR; size * size * size];
+    assert_eq!(chunk.bits, 0);
+
+    for block in 1..40 {
+      for _ in 0..16 {
+        let (x, y, z) = (
+          rng.gen_range(0..size),
+          rng.gen_range(0..size),
+          rng.gen_range(0..size),
+        );
+        chunk.set(x, y, z, block);
+        voxels[chunk.index(x, y, z)] = block;
+      }
+      assert_eq!(chunk.to_voxels(), voxels);
+
+      // the indices grow when the palette outgrows 1, 2, 4 and 16 blocks
+      match block {
+        1 => assert_eq!(chunk.bits, 1),
+        3 => assert_eq!(chunk.bits, 2),
+        15 => assert_eq!(chunk.bits, 4),
+        16 => assert_eq!(chunk.bits, 8),
+        _ => (),
+      }
+    }
+
+    assert_eq!(
+      Chunk::from_voxels(size, voxels.clone())
+        .unwrap()
+        .to_voxels(),
+      voxels
+    );
+  }
+
+  #[test]
+  fn few_blocks_use_less_memory() {
+    let size = 32;
+    let raw = size * size * size * std::mem::size_of::<BlockId>();
+
+    assert!(Chunk::new(size).memory_usage() < 128);
+    let chunk = Chunk::random(size, &[blocks::AIR, blocks::STONE]);
+    assert!(chunk.memory_usage() * 4 < raw);
+    assert!(Chunk::from_voxels(size, vec![blocks::AIR; 7]).is_none());
+  }
+
+  #[test]
+  fn compacted_chunks_become_uniform() {
+    let size = 16;
+    let mut chunk = Chunk::filled(size, blocks::STONE);
+    assert_eq!(chunk.uniform_block(), Some(blocks::STONE));
+    assert_eq!(chunk.memory_usage(), Chunk::new(size).memory_usage());
+
+    chunk.set(1, 2, 3, blocks::DIRT);
+    chunk.set(4, 5, 6, blocks::GLASS);
+    assert_eq!(chunk.uniform_block(), None);
+
+    chunk.set(1, 2, 3, blocks::STONE);
+    let voxels = chunk.to_voxels();
+    chunk.compact();
+    assert_eq!(chunk.palette(), &[blocks::STONE, blocks::GLASS]);
+    assert_eq!(chunk.to_voxels(), voxels);
+
+    chunk.set(4, 5, 6, blocks::STONE);
+    chunk.compact();
+    assert_eq!(chunk.uniform_block(), Some(blocks::STONE));
+    assert_eq!(chunk.get(4, 5, 6), blocks::STONE);
+  }
+}
@@ -0,0 +1,182 @@
+use bevy::prelude::*;
+
+/**
+ * How close a box can get to a voxel it collides with, so that it never ends up overlapping it because of rounding
+ */
+const SKIN: f32 = 1e-4;
+
+/**
+ * An axis aligned box, used as the collision shape of moving entities
+ * `min`: the corner with the smallest coordinates
+ * `max`: the corner with the biggest coordinates
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+  pub min: Vec3,
+  pub max: Vec3,
+}
+
+impl Aabb {
+  pub fn new(min: Vec3, max: Vec3) -> Self {
+    Self { min, max }
+  }
+
+  /**
+   * Returns a box standing on `feet`, centered on it horizontally
+   * `half_width`: half of the size of the box along x and z
+   * `height`: the size of the box along y
+   */
+  pub fn from_feet(feet: Vec3, half_width: f32, height: f32) -> Self {
+    Self {
+      min: feet - Vec3::new(half_width, 0.0, half_width),
+      max: feet + Vec3::new(half_width, height, half_width),
+    }
+  }
+
+  pub fn translated(&self, offset: Vec3) -> Self {
+    Self {
+      min: self.min + offset,
+      max: self.max + offset,
+    }
+  }
+
+  /**
+   * Returns wether the box overlaps a voxel, touching it doesn't count as overlapping it
+   */
+  pub fn overlaps_voxel(&self, voxel: IVec3) -> bool {
+    (0..3).all(|axis| self.voxel_range(axis).contains(&voxel[axis]))
+  }
+
+  /**
+   * Returns the voxels the box overlaps on an axis, touching a voxel doesn't count as overlapping it
+   */
+  fn voxel_range(&self, axis: usize) -> std::ops::Range<i32> {
+    (self.min[axis] + SKIN).floor() as i32..(self.max[axis] - SKIN).ceil() as i32
+  }
+}
+
+/**
+ * The result of moving a box through the voxels (see `sweep`)
+ * `motion`: how far the box actually moved
+ * `collided`: the axes on which the box was stopped by a voxel
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sweep {
+  pub motion: Vec3,
+  pub collided: BVec3,
+}
+
+/**
+ * Moves a box as far as possible towards `motion` without entering solid voxels
+ * the box is moved one axis at a time (y first, then x and z), so it slides along the voxels it hits instead of stopping
+ * voxels the box already overlaps are ignored, so a box stuck inside a wall can still walk out of it
+ * `aabb`: the box to move
+ * `motion`: how far the box wants to move
+ * `solid`: returns wether the voxel at the given coordinates stops the box
+ */
+pub fn sweep(aabb: &Aabb, motion: Vec3, solid: impl Fn(IVec3) -> bool) -> Sweep {
+  let mut aabb = *aabb;
+  let mut moved = Vec3::ZERO;
+  let mut collided = [false; 3];
+
+  for axis in [1, 0, 2] {
+    let wanted = motion[axis];
+    if wanted == 0.0 {
+      continue;
+    }
+
+    let (u, v) = ((axis + 1) % 3, (axis + 2) % 3);
+    let (u_range, v_range) = (aabb.voxel_range(u), aabb.voxel_range(v));
+    let layer_solid = |layer: i32| {
+      u_range.clone().any(|a| {
+        v_range.clone().any(|b| {
+          let mut voxel = IVec3::ZERO;
+          voxel[axis] = layer;
+          voxel[u] = a;
+          voxel[v] = b;
+          solid(voxel)
+        })
+      })
+    };
+
+    // walk the layers of voxels in front of the box, up to where it wants to go
+    let mut allowed = wanted;
+    if wanted > 0.0 {
+      let front = aabb.max[axis];
+      let mut layer = (front - SKIN).ceil() as i32;
+      while (layer as f32) < front + wanted {
+        if layer_solid(layer) {
+          allowed = (layer as f32 - front - SKIN).clamp(0.0, wanted);
+          collided[axis] = true;
+          break;
+        }
+        layer += 1;
+      }
+    } else {
+      let front = aabb.min[axis];
+      let mut layer = (front + SKIN).floor() as i32 - 1;
+      while (layer + 1) as f32 > front + wanted {
+        if layer_solid(layer) {
+          allowed = ((layer + 1) as f32 - front + SKIN).clamp(wanted, 0.0);
+          collided[axis] = true;
+          break;
+        }
+        layer -= 1;
+      }
+    }
+
+    let mut offset = Vec3::ZERO;
+    offset[axis] = allowed;
+    aabb = aabb.translated(offset);
+    moved[axis] = allowed;
+  }
+
+  Sweep {
+    motion: moved,
+    collided: BVec3::new(collided[0], collided[1], collided[2]),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn boxes_stop_at_solid_voxels() {
+    // a floor at y = 0 and a wall at x = 3
+    let solid = |voxel: IVec3| voxel.y <= 0 || voxel.x == 3;
+    let player = Aabb::from_feet(Vec3::new(0.5, 1.0, 0.5), 0.3, 1.8);
+
+    // falling onto the floor
+    let fall = sweep(&player.translated(Vec3::Y * 2.0), Vec3::NEG_Y * 5.0, solid);
+    assert!(fall.collided.y);
+    assert!((fall.motion.y + 2.0).abs() < 1e-3);
+
+    // walking into the wall slides along it
+    let walk = sweep(&player, Vec3::new(4.0, 0.0, 2.5), solid);
+    assert_eq!(walk.collided, BVec3::new(true, false, false));
+    assert!((player.max.x + walk.motion.x - 3.0).abs() < 1e-3);
+    assert!(player.max.x + walk.motion.x <= 3.0);
+    assert_eq!(walk.motion.z, 2.5);
+
+    // standing on the floor doesn't count as overlapping it
+    let free = sweep(&player, Vec3::new(-1.0, 0.5, 0.0), solid);
+    assert_eq!(free.collided, BVec3::FALSE);
+    assert_eq!(free.motion, Vec3::new(-1.0, 0.5, 0.0));
+
+    assert!(player.overlaps_voxel(IVec3::new(0, 1, 0)));
+    assert!(player.overlaps_voxel(IVec3::new(0, 2, 0)));
+    assert!(!player.overlaps_voxel(IVec3::new(0, 0, 0)));
+    assert!(!player.overlaps_voxel(IVec3::new(1, 1, 0)));
+  }
+
+  #[test]
+  fn fast_boxes_dont_tunnel() {
+    let solid = |voxel: IVec3| voxel == IVec3::new(0, -10, 0);
+    let player = Aabb::from_feet(Vec3::new(0.5, 0.0, 0.5), 0.3, 1.8);
+
+    let fall = sweep(&player, Vec3::NEG_Y * 100.0, solid);
+    assert!(fall.collided.y);
+    assert!((fall.motion.y + 9.0).abs() < 1e-3);
+  }
+}
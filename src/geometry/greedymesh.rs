@@ -1,129 +1,115 @@
-use bevy::prelude::*;
-use rand::prelude::*;
-use bevy::render::mesh::{self, PrimitiveTopology};
+use super::padded::PaddedChunk;
+use super::quad::{expand_direction, FaceKey, Quad};
+use crate::block::BlockRegistry;
 
-// NOTE: the algorithm can be sped up EXTREMELY by using stack allocated arrays instead of vectors
-//       but this requires increasing the size of the stack on the main thread.
-
-pub struct Chunk {
-  pub size: usize,
-  pub voxels: Vec<Vec<Vec<u8>>>,
+/**
+ * The set of faces that still need to be meshed
+ * holds one slab of `size^3` entries per direction, each slab is indexed like the voxels of a chunk (see `Chunk::index`)
+ * an entry is `None` if there is no face to mesh, or the key of the face otherwise
+ */
+struct FaceQueue {
+  size: usize,
+  volume: usize,
+  faces: Vec<Option<FaceKey>>,
 }
 
-impl Chunk {
-  pub fn new(size: usize) -> Self {
-    // let mut voxels = [[[1; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE];
-    let mut voxels = vec![vec![vec![1; size]; size]; size];
-
-    for x in 0..size {
-      for y in 0..size {
-        for z in 0..size {
-          voxels[x][y][z] = random::<u8>() % 2;
-        }
-      }
+impl FaceQueue {
+  fn new(size: usize) -> Self {
+    let volume = size * size * size;
+    Self {
+      size,
+      volume,
+      faces: vec![None; volume * 6],
     }
+  }
 
-    Self { size, voxels }
+  #[inline]
+  fn index(&self, face: &(usize, usize, usize)) -> usize {
+    (face.0 * self.size + face.1) * self.size + face.2
   }
 
-  pub fn print(&self) {
-    for z in 0..self.size {
-      print!("y|x ");
-      for y in 0..self.size {
-        print!("{} ", y);
-      }
-      println!("");
+  #[inline]
+  fn position(&self, index: usize) -> (usize, usize, usize) {
+    (
+      index / (self.size * self.size),
+      index / self.size % self.size,
+      index % self.size,
+    )
+  }
 
-      print!(" +-");
-      for _ in 0..self.size {
-        print!("--");
-      }
-      println!("   z = {}", z);
+  #[inline]
+  fn get(&self, direction: usize, index: usize) -> Option<FaceKey> {
+    self.faces[direction * self.volume + index]
+  }
 
-      for y in 0..self.size {
-        print!("{}|  ", y);
-        for x in 0..self.size {
-          print!("{} ", self.voxels[x][y][z]);
-        }
-        println!();
-      }
-      println!();
-    }
+  #[inline]
+  fn set(&mut self, direction: usize, index: usize, value: Option<FaceKey>) {
+    self.faces[direction * self.volume + index] = value;
   }
-}
 
-// type FaceQueue = [[[[u8; CHUNK_SIZE]; CHUNK_SIZE]; CHUNK_SIZE]; 6];
-type FaceQueue = Vec<Vec<Vec<Vec<u8>>>>;
+  /**
+   * Returns the entries of all the faces facing `direction`
+   */
+  #[inline]
+  fn slab(&self, direction: usize) -> &[Option<FaceKey>] {
+    &self.faces[direction * self.volume..(direction + 1) * self.volume]
+  }
+}
 
 /**
- * Returns wether a face is NOT hidden by another voxel
+ * Returns wether a face is NOT hidden by another voxel (see `BlockRegistry::face_hidden`)
+ * faces on the border of the chunk are checked against the voxels of the neighbouring chunks
  * `face`: the face to check
  * `direction`: the direction of the face (0 = right, 1 = left, 2 = up, 3 = down, 4 = front, 5 = back)
  * `chunk`: the chunk to check
+ * `registry`: the registry used to look up the properties of the voxels
  */
-fn face_visible(face: &(usize, usize, usize), direction: usize, chunk: &Chunk) -> bool {
-  if direction == 0 {
+pub fn face_visible(
+  face: &(usize, usize, usize),
+  direction: usize,
+  chunk: &PaddedChunk,
+  registry: &BlockRegistry,
+) -> bool {
+  let (x, y, z) = (face.0 as i32, face.1 as i32, face.2 as i32);
+
+  let neighbour = match direction {
     // right
-    if face.0 == chunk.size - 1 {
-      return true;
-    }
-    chunk.voxels[face.0 + 1][face.1][face.2] == 0
-  } else if direction == 1 {
+    0 => chunk.get(x + 1, y, z),
     // left
-    if face.0 == 0 {
-      return true;
-    }
-    chunk.voxels[face.0 - 1][face.1][face.2] == 0
-  } else if direction == 2 {
+    1 => chunk.get(x - 1, y, z),
     // up
-    if face.1 == chunk.size - 1 {
-      return true;
-    }
-    chunk.voxels[face.0][face.1 + 1][face.2] == 0
-  } else if direction == 3 {
+    2 => chunk.get(x, y + 1, z),
     // down
-    if face.1 == 0 {
-      return true;
-    }
-    chunk.voxels[face.0][face.1 - 1][face.2] == 0
-  } else if direction == 4 {
+    3 => chunk.get(x, y - 1, z),
     // front
-    if face.2 == chunk.size - 1 {
-      return true;
-    }
-    chunk.voxels[face.0][face.1][face.2 + 1] == 0
-  } else if direction == 5 {
+    4 => chunk.get(x, y, z + 1),
     // back
-    if face.2 == 0 {
-      return true;
-    }
-    chunk.voxels[face.0][face.1][face.2 - 1] == 0
-  } else {
-    panic!("invalid direction");
-  }
+    5 => chunk.get(x, y, z - 1),
+    _ => panic!("invalid direction"),
+  };
+
+  !registry.face_hidden(chunk.get(x, y, z), neighbour)
 }
 
 /**
- * Returns wether the face exists in the set
+ * Returns wether the face exists in the set with the given key
  * `face`: the face to check
  * `direction`: the direction of the face (0 = right, 1 = left, 2 = up, 3 = down, 4 = front, 5 = back)
- * `face_set`: the set of faces to check
+ * `key`: the key the face must have, faces with a different key can't be merged
+ * `face_queue`: the set of faces to check
  */
 fn face_exists(
   face: &(usize, usize, usize),
   direction: usize,
-  face_set: &FaceQueue,
-  chunk: &Chunk,
+  key: FaceKey,
+  face_queue: &FaceQueue,
 ) -> bool {
-  // println!("face set: {:?}", face_set[direction]);
-  if face.0 >= chunk.size || face.1 >= chunk.size || face.2 >= chunk.size {
+  let size = face_queue.size;
+  if face.0 >= size || face.1 >= size || face.2 >= size {
     return false;
   }
 
-  let exists = face_set[direction][face.0][face.1][face.2] == 1;
-  // println!("-- face exists? {:?} {:?}", face, exists);
-
-  exists
+  face_queue.get(direction, face_queue.index(face)) == Some(key)
 }
 
 /**
@@ -132,21 +118,19 @@ fn face_exists(
  * `width`: the width of the row
  * `direction`: the direction the row is facing (0 = right, 1 = left, 2 = up, 3 = down, 4 = front, 5 = back)
  * `face`: the face that the row is based on
- * `face_set`: the set of faces to check
- * `chunk`: the chunk to check
+ * `key`: the key of the faces in the row
+ * `face_queue`: the set of faces to check
  */
 fn can_extend_row(
   height: usize,
   width: usize,
   direction: usize,
   face: &(usize, usize, usize),
-  face_set: &FaceQueue,
-  chunk: &Chunk,
+  key: FaceKey,
+  face_queue: &FaceQueue,
 ) -> bool {
   let expand = expand_direction(direction);
 
-  // println!("- can extend row? {:?}", face);
-
   (0..width).all(|w| {
     let next_face = (
       face.0 + expand.width[0] * w + expand.height[0] * height,
@@ -154,179 +138,101 @@ fn can_extend_row(
       face.2 + expand.width[2] * w + expand.height[2] * height,
     );
 
-    // println!("-- looking at face {:?}, w: {}, h: {}", next_face, w, height);
-
-    face_exists(&next_face, direction, face_set, chunk)
-      && face_visible(&next_face, direction, chunk)
+    face_exists(&next_face, direction, key, face_queue)
   })
 }
 
 /**
  * Utils struct that keeps track of the next face to check
- * `next_face`: the next face to check
+ * `next_index`: the index (in the face queue slab) of the next face to check
  */
 struct FaceFinder {
-  next_face: (usize, usize, usize),
+  next_index: usize,
 }
 
 impl FaceFinder {
   fn new() -> Self {
-    Self {
-      next_face: (0, 0, 0),
-    }
+    Self { next_index: 0 }
   }
 
   /**
    * Finds the next face in the chunk to check
-   * `face_set`: the set of faces to check
-   * `chunk`: the chunk to check
+   * faces before `next_index` are never looked at again, since they have already been removed from the queue
+   * `face_queue`: the set of faces to check
    * `direction`: the direction of the face (0 = right, 1 = left, 2 = up, 3 = down, 4 = front, 5 = back)
+   * returns the face and its key
    */
   fn next_face(
     &mut self,
-    face_set: &FaceQueue,
-    chunk: &Chunk,
+    face_queue: &FaceQueue,
     direction: usize,
-  ) -> Option<(usize, usize, usize)> {
-    // println!("-- looking for next face starting with {:?}", to_test);
-    while self.next_face.0 < chunk.size {
-      while self.next_face.1 < chunk.size {
-        while self.next_face.2 < chunk.size {
-          // println!("-- looking at face {:?}", self.next_face);
-          // if face_set[direction].contains(&(self.next_face.0, self.next_face.1, self.next_face.2)) {
-          if face_set[direction][self.next_face.0][self.next_face.1][self.next_face.2] == 1 {
-            // println!("found next face! {:?}", self.next_face);
-            return Some(self.next_face);
-          }
-          self.next_face.2 += 1;
-        }
-        self.next_face.2 = 0;
-        self.next_face.1 += 1;
-      }
-      self.next_face.1 = 0;
-      self.next_face.0 += 1;
-    }
-
-    return None;
+  ) -> Option<((usize, usize, usize), FaceKey)> {
+    let slab = face_queue.slab(direction);
+    let offset = slab[self.next_index..].iter().position(Option::is_some)?;
+    self.next_index += offset;
 
-    // panic!("No next face found! (to_test = {:?})", &self.next_face); // should not happen in the algorithm
+    Some((face_queue.position(self.next_index), slab[self.next_index]?))
   }
 }
 
-struct ExpandDirection {
-  width: [usize; 3],
-  height: [usize; 3],
-}
-
 /**
- * Returns the expansion direction for a given direction
- * `direction`: the direction to expand (0 = right, 1 = left, 2 = up, 3 = down, 4 = front, 5 = back)
- * returns an `ExpandDirection` struct, containing the expansion directions for the width and height
- * an `ExpandDirection` struct contains 3 values, one for each axis (x, y, z), a face should be expanded in the axis with a value of 1, and not expanded in the axis with a value of 0
+ * Runs the greedy meshing algorithm on a chunk and returns the resulting quads
+ * only faces with the same key (see `FaceKey`) are merged together, so faces with different ambient occlusion or light are never merged
+ * `chunk`: the chunk to run the algorithm on, its border is only used to cull faces
+ * `registry`: the registry used to decide which faces are visible
  */
-fn expand_direction(direction: usize) -> ExpandDirection {
-  match direction {
-    // left - right
-    0 => ExpandDirection {
-      width: [0, 0, 1],
-      height: [0, 1, 0],
-    },
-    1 => ExpandDirection {
-      width: [0, 0, 1],
-      height: [0, 1, 0],
-    },
-    // up - down
-    2 => ExpandDirection {
-      width: [1, 0, 0],
-      height: [0, 0, 1],
-    },
-    3 => ExpandDirection {
-      width: [1, 0, 0],
-      height: [0, 0, 1],
-    },
-    // front - back
-    4 => ExpandDirection {
-      width: [1, 0, 0],
-      height: [0, 1, 0],
-    },
-    5 => ExpandDirection {
-      width: [1, 0, 0],
-      height: [0, 1, 0],
-    },
-
-    _ => panic!("Invalid direction!"),
-  }
+pub fn greedy_quads(chunk: &PaddedChunk, registry: &BlockRegistry) -> Vec<Quad> {
+  let size = chunk.size;
+
+  // every face of every voxel that has something to render
+  let faces = (0..size * size * size)
+    .map(|i| (i / (size * size), i / size % size, i % size))
+    .filter(|face| {
+      let voxel = chunk.get(face.0 as i32, face.1 as i32, face.2 as i32);
+      registry.get(voxel).has_faces()
+    })
+    .flat_map(|face| (0..6).map(move |d| (face, d)));
+
+  greedy_merge(chunk, registry, faces)
 }
 
 /**
- * Runs the greedy meshing algorithm on a chunk
- * `chunk`: the chunk to run the algorithm on
+ * Merges some faces of a chunk into quads with the greedy meshing algorithm (see `greedy_quads`)
+ * the hidden faces are skipped, the quads are sorted by direction, then by the index of their first face
+ * `chunk`: the chunk the faces are in, its border is only used to cull faces
+ * `registry`: the registry used to decide which faces are visible
+ * `faces`: the faces to merge and their direction, the voxels of the faces must have something to render
  */
-pub fn greedy_mesh(chunk: &Chunk) -> Mesh {
+pub fn greedy_merge(
+  chunk: &PaddedChunk,
+  registry: &BlockRegistry,
+  faces: impl IntoIterator<Item = ((usize, usize, usize), usize)>,
+) -> Vec<Quad> {
   // the queue of faces that still need to be meshed
-  let mut face_queue: FaceQueue = vec![vec![vec![vec![0; chunk.size]; chunk.size]; chunk.size]; 6];
-
-  // for each voxel in the chunk...
-  for x in 0..chunk.size {
-    for y in 0..chunk.size {
-      for z in 0..chunk.size {
-        // ...if the voxel is not empty...
-        if chunk.voxels[x][y][z] == 0 {
-          continue;
-        }
-
-        // ...for each direction...
-        for d in 0..6 {
-          let face = (x, y, z);
+  let mut face_queue = FaceQueue::new(chunk.size);
 
-          // ...if the face is visible we add it to the queue
-          if face_visible(&face, d, chunk) {
-            // println!("face {:?} visible", face);
-            // face_set[d].insert((x, y, z));
-            face_queue[d][x][y][z] = 1;
-          }
-        }
-      }
+  // if the face is visible we add it to the queue
+  for (face, d) in faces {
+    if face_visible(&face, d, chunk, registry) {
+      let index = face_queue.index(&face);
+      face_queue.set(d, index, Some(FaceKey::new(&face, d, chunk, registry)));
     }
   }
-  
-  let mut vertices: Vec<[f32; 3]> = Vec::new();
-  let mut indices: Vec<u32> = Vec::new();
-  let mut normals: Vec<[f32; 3]> = Vec::new();
+
+  let mut quads = Vec::new();
 
   // for each direction...
-  for d in [0, 1, 2, 3, 4, 5].iter() {
-    let d = *d;
+  for d in 0..6 {
     let expand = expand_direction(d);
     let mut face_finder = FaceFinder::new();
 
-    // println!("{:?} \n", face_set[d]);
-
     // ...we loop until there are no more faces to check
-    loop {
-      // find the next face to test
-      let face = face_finder.next_face(&face_queue, chunk, d);
-
-      // if there are no more faces to test, we break the loop
-      if face.is_none() {
-        break;
-      }
-      let face = face.unwrap();
-
-      // println!("starting face at {:?}", face);
-
+    // (only visible faces are ever queued, so there is no need to check visibility again)
+    while let Some((face, key)) = face_finder.next_face(&face_queue, d) {
       // remove the face from the queue
-      face_queue[d][face.0][face.1][face.2] = 0;
-
-      // check if the face is visible, if not, we skip it (note: the face is still removed from the queue)
-      if !face_visible(&face, d, chunk) {
-        // println!("face {:?} not visible", face);
-        continue;
-      }
+      face_queue.set(d, face_queue.index(&face), None);
 
-      // println!("testing face {:?}", face);
-
-      // while the adjacent face is visible, we expand the face in the width direction
+      // while the adjacent face is queued with the same key, we expand the face in the width direction
       let mut width = 1;
       let mut next_face = (
         face.0 + expand.width[0] * width,
@@ -334,12 +240,8 @@ pub fn greedy_mesh(chunk: &Chunk) -> Mesh {
         face.2 + expand.width[2] * width,
       );
 
-      // println!("next face: {:?}", next_face);
-
-      while face_exists(&next_face, d, &face_queue, chunk) && face_visible(&next_face, d, chunk) {
-        // face_set[d].remove(&(next_face.0, next_face.1, next_face.2));
-        face_queue[d][next_face.0][next_face.1][next_face.2] = 0;
-        // println!("extended to face {:?}", next_face);
+      while face_exists(&next_face, d, key, &face_queue) {
+        face_queue.set(d, face_queue.index(&next_face), None);
         width += 1;
         next_face = (
           face.0 + expand.width[0] * width,
@@ -348,83 +250,233 @@ pub fn greedy_mesh(chunk: &Chunk) -> Mesh {
         );
       }
 
-      // println!("width: {}", width);
-
       // if possible, we expand the whole row of faces in the height direction
       let mut height = 1;
-      while can_extend_row(height, width, d, &face, &face_queue, chunk) {
-        // println!("can extend row {}!", height);
+      while can_extend_row(height, width, d, &face, key, &face_queue) {
         // remove the faces from the queue
         for w in 0..width {
-          face_queue[d][face.0 + expand.width[0] * w + expand.height[0] * height]
-            [face.1 + expand.width[1] * w + expand.height[1] * height]
-            [face.2 + expand.width[2] * w + expand.height[2] * height] = 0;
+          let index = face_queue.index(&(
+            face.0 + expand.width[0] * w + expand.height[0] * height,
+            face.1 + expand.width[1] * w + expand.height[1] * height,
+            face.2 + expand.width[2] * w + expand.height[2] * height,
+          ));
+          face_queue.set(d, index, None);
         }
 
         height += 1;
       }
 
-      // TODO: generate mesh for the face OR add the face to the list of faces to generate meshes for
-      // println!("Face: {:?}, Width: {}, Height: {}, direction {} \n", face, width, height, d);
-
-      let extra_x = if d == 0 { 1.0 } else { 0.0 };
-      let extra_y = if d == 2 { 1.0 } else { 0.0 };
-      let extra_z = if d == 4 { 1.0 } else { 0.0 };
-
-      vertices.push([
-        face.0 as f32 + extra_x,
-        face.1 as f32 + extra_y,
-        face.2 as f32 + extra_z
-      ]);
-      vertices.push([
-        face.0 as f32 + expand.width[0] as f32 * width as f32 + extra_x,
-        face.1 as f32 + expand.width[1] as f32 * width as f32 + extra_y,
-        face.2 as f32 + expand.width[2] as f32 * width as f32 + extra_z,
-      ]);
-      vertices.push([
-        face.0 as f32 + expand.width[0] as f32 * width as f32 + expand.height[0] as f32 * height as f32 + extra_x,
-        face.1 as f32 + expand.width[1] as f32 * width as f32 + expand.height[1] as f32 * height as f32 + extra_y,
-        face.2 as f32 + expand.width[2] as f32 * width as f32 + expand.height[2] as f32 * height as f32 + extra_z,
-      ]);
-      vertices.push([
-        face.0 as f32 + expand.height[0] as f32 * height as f32 + extra_x,
-        face.1 as f32 + expand.height[1] as f32 * height as f32 + extra_y,
-        face.2 as f32 + expand.height[2] as f32 * height as f32 + extra_z,
-      ]);
-
-      let mut idx = vec![0, 1, 2, 2, 3, 0];
-
-      if d == 0 || d == 2 || d == 5 {
-        idx.reverse();
+      quads.push(Quad {
+        position: face,
+        direction: d,
+        width,
+        height,
+        key,
+      });
+    }
+  }
+
+  quads
+}
+
+#[cfg(test)]
+mod tests {
+  use bevy::prelude::*;
+  use bevy::render::mesh::{Indices, VertexAttributeValues};
+  use bevy::utils::HashSet;
+  use rand::prelude::*;
+
+  use super::*;
+  use crate::block::{blocks, BlockId};
+  use crate::geometry::chunk::Chunk;
+  use crate::geometry::naivemesh::face_quads;
+  use crate::geometry::padded::PaddedChunk;
+  use crate::geometry::quad::{direction_normal, direction_offset, QuadMeshBuilder};
+
+  type Face = ((usize, usize, usize), usize);
+
+  /**
+   * The chunks every test runs on: hand-crafted edge cases, then random chunks with and without neighbours
+   */
+  fn test_chunks() -> Vec<PaddedChunk> {
+    let mut chunks = Vec::new();
+
+    let empty = Chunk::new(8);
+    chunks.push(PaddedChunk::from_chunk(&empty));
+
+    let mut full = Chunk::new(8);
+    let mut single = Chunk::new(8);
+    let mut checkerboard = Chunk::new(8);
+    let mut pillar = Chunk::new(8);
+    for ((x, y, z), _) in empty.iter() {
+      full.set(x, y, z, blocks::STONE);
+      if (x + y + z) % 2 == 0 {
+        checkerboard.set(x, y, z, blocks::DIRT);
       }
+      if x == 3 && z == 3 {
+        pillar.set(x, y, z, if y % 3 == 0 { blocks::GLASS } else { blocks::STONE });
+      }
+    }
+    single.set(0, 7, 4, blocks::GRASS);
 
-      for i in 0..idx.len() { idx[i] += (vertices.len() - 4) as u32; }
-      indices.append(&mut idx);
-
-      for _ in 0..4 {
-        normals.push( match d {
-          0 => [1.0, 0.0, 0.0],
-          1 => [-1.0, 0.0, 0.0],
-          2 => [0.0, 1.0, 0.0],
-          3 => [0.0, -1.0, 0.0],
-          4 => [0.0, 0.0, 1.0],
-          5 => [0.0, 0.0, -1.0],
-          _ => panic!("Invalid direction!"),
-        });
+    for chunk in [&full, &single, &checkerboard, &pillar] {
+      chunks.push(PaddedChunk::from_chunk(chunk));
+    }
+    // surrounded by stone, so only the faces towards the inside of the chunk are left
+    chunks.push(PaddedChunk::from_neighbours(&checkerboard, |_| Some(&full)));
+
+    let mut rng = StdRng::seed_from_u64(14);
+    let palettes: [&[BlockId]; 3] = [
+      &[blocks::AIR, blocks::STONE],
+      &[blocks::STONE, blocks::DIRT, blocks::GRASS],
+      &[blocks::AIR, blocks::AIR, blocks::STONE, blocks::GLASS, blocks::WATER, blocks::LEAVES],
+    ];
+
+    for size in [1, 2, 7, 16] {
+      for palette in palettes {
+        let chunk = Chunk::random_with(&mut rng, size, palette);
+        let neighbour = Chunk::random_with(&mut rng, size, palette);
+
+        chunks.push(PaddedChunk::from_chunk(&chunk));
+        chunks.push(PaddedChunk::from_neighbours(&chunk, |_| Some(&neighbour)));
       }
     }
+
+    chunks
   }
 
-  let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+  /**
+   * The faces that should be meshed, straight from the culling rules of the registry
+   */
+  fn visible_faces(chunk: &PaddedChunk, registry: &BlockRegistry) -> HashSet<Face> {
+    let mut faces = HashSet::new();
+    let size = chunk.size as i32;
 
-  // println!("Vertices: {:?} {:?} ", vertices.len(), vertices);
-  // println!("Indices: {:?} {:?}", indices.len(), indices);
-  // println!("Normals: {:?} {:?}", normals.len(), normals);
+    for x in 0..size {
+      for y in 0..size {
+        for z in 0..size {
+          let block = chunk.get(x, y, z);
+          if !registry.get(block).has_faces() {
+            continue;
+          }
 
-  mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
-  mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, normals);
-  mesh.set_indices(Some(bevy::render::mesh::Indices::U32(indices)));
-  
+          for d in 0..6 {
+            let n = IVec3::new(x, y, z) + direction_offset(d);
+            if !registry.face_hidden(block, chunk.get(n.x, n.y, n.z)) {
+              faces.insert(((x as usize, y as usize, z as usize), d));
+            }
+          }
+        }
+      }
+    }
+
+    faces
+  }
+
+  /**
+   * Every face covered by a quad, in the order they are covered
+   */
+  fn covered_faces(quad: &Quad) -> Vec<Face> {
+    let expand = expand_direction(quad.direction);
+    let mut faces = Vec::new();
+
+    for w in 0..quad.width {
+      for h in 0..quad.height {
+        let p = quad.position;
+        let face = (
+          p.0 + expand.width[0] * w + expand.height[0] * h,
+          p.1 + expand.width[1] * w + expand.height[1] * h,
+          p.2 + expand.width[2] * w + expand.height[2] * h,
+        );
+        faces.push((face, quad.direction));
+      }
+    }
+
+    faces
+  }
+
+  #[test]
+  fn quads_cover_exactly_the_visible_faces() {
+    let registry = BlockRegistry::default();
+
+    for chunk in test_chunks() {
+      let expected = visible_faces(&chunk, &registry);
+      let mut covered = HashSet::new();
+
+      for quad in greedy_quads(&chunk, &registry) {
+        for face in covered_faces(&quad) {
+          let (p, _) = face;
+          assert!(covered.insert(face), "face {face:?} is covered twice");
+          assert_eq!(
+            chunk.get(p.0 as i32, p.1 as i32, p.2 as i32),
+            quad.key.block,
+            "quad {quad:?} covers a face of another block"
+          );
+        }
+      }
+
+      let missing: Vec<_> = expected.difference(&covered).collect();
+      let extra: Vec<_> = covered.difference(&expected).collect();
+      assert!(missing.is_empty(), "faces without a quad: {missing:?}");
+      assert!(extra.is_empty(), "quads over hidden faces: {extra:?}");
+    }
+  }
+
+  #[test]
+  fn area_matches_per_face_mesher() {
+    let registry = BlockRegistry::default();
+
+    for chunk in test_chunks() {
+      let area: usize = greedy_quads(&chunk, &registry)
+        .iter()
+        .map(|quad| quad.width * quad.height)
+        .sum();
+
+      assert_eq!(area, face_quads(&chunk, true, &registry).len());
+    }
+  }
+
+  #[test]
+  fn triangles_face_the_quad_direction() {
+    let registry = BlockRegistry::default();
+
+    for chunk in test_chunks() {
+      for quad in greedy_quads(&chunk, &registry) {
+        let mut builder = QuadMeshBuilder::new();
+        builder.push_quad(&quad, &registry);
+        let mesh = builder.build();
+
+        let Some(VertexAttributeValues::Float32x3(positions)) = mesh.attribute(Mesh::ATTRIBUTE_POSITION) else {
+          panic!("missing positions");
+        };
+        let Some(VertexAttributeValues::Float32x3(normals)) = mesh.attribute(Mesh::ATTRIBUTE_NORMAL) else {
+          panic!("missing normals");
+        };
+        let Some(Indices::U32(indices)) = mesh.indices() else {
+          panic!("missing indices");
+        };
+
+        let expected = Vec3::from(direction_normal(quad.direction));
+        assert!(normals.iter().all(|normal| Vec3::from(*normal) == expected));
+        assert_eq!(indices.len(), 6);
+
+        // counter clockwise triangles are front facing, so their normal must point the same way as the face
+        for triangle in indices.chunks(3) {
+          let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(positions[triangle[i] as usize]));
+          let normal = (b - a).cross(c - a);
+
+          assert!(
+            normal.normalize().abs_diff_eq(expected, 1e-5),
+            "quad {quad:?} has a triangle facing {normal}"
+          );
+          assert_eq!(normal.length(), (quad.width * quad.height) as f32, "degenerate triangle in {quad:?}");
+        }
 
-  return mesh;
+        // the quad lies on the side of its voxel it is facing
+        let origin = Vec3::new(quad.position.0 as f32, quad.position.1 as f32, quad.position.2 as f32);
+        let plane = (origin + Vec3::splat(0.5) + expected * 0.5).dot(expected);
+        assert!(positions.iter().all(|p| Vec3::from(*p).dot(expected) == plane));
+      }
+    }
+  }
 }
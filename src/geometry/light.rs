@@ -0,0 +1,140 @@
+use super::padded::PaddedChunk;
+use super::quad::direction_offset;
+
+/**
+ * The brightest light level, the level of direct sunlight
+ */
+pub const MAX_LIGHT: u8 = 15;
+
+/**
+ * The light of a voxel, two levels from 0 to `MAX_LIGHT` packed in a byte
+ * the sky light (high 4 bits) comes from the sun, the block light (low 4 bits) from emissive blocks (see `BlockProperties::emissive`)
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug, Default)]
+pub struct Light(pub u8);
+
+impl Light {
+  /**
+   * The light of a voxel that sees the sky
+   */
+  pub const SKY: Self = Self(MAX_LIGHT << 4);
+
+  pub fn new(sky: u8, block: u8) -> Self {
+    debug_assert!(sky <= MAX_LIGHT && block <= MAX_LIGHT);
+    Self(sky << 4 | block)
+  }
+
+  #[inline]
+  pub fn sky(self) -> u8 {
+    self.0 >> 4
+  }
+
+  #[inline]
+  pub fn block(self) -> u8 {
+    self.0 & 0xF
+  }
+
+  pub fn with_sky(self, sky: u8) -> Self {
+    Self::new(sky, self.block())
+  }
+
+  pub fn with_block(self, block: u8) -> Self {
+    Self::new(self.sky(), block)
+  }
+}
+
+/**
+ * Returns how bright a light level looks, from 0 to 1
+ * every level is 80% as bright as the one above it, which looks about linear to the eye
+ */
+pub fn light_brightness(level: u8) -> f32 {
+  0.8f32.powi((MAX_LIGHT - level.min(MAX_LIGHT)) as i32)
+}
+
+/**
+ * The light of every voxel of a chunk
+ * like the voxels of a chunk with a single block, a chunk where every voxel has the same light doesn't use memory for them
+ * `uniform`: the light of every voxel, if `values` is empty
+ * `values`: the light of each voxel, in the layout of `Chunk::index`
+ */
+#[derive(Clone, Debug)]
+pub struct LightField {
+  uniform: Light,
+  values: Vec<Light>,
+}
+
+impl LightField {
+  pub fn new(light: Light) -> Self {
+    Self {
+      uniform: light,
+      values: Vec::new(),
+    }
+  }
+
+  #[inline]
+  pub fn get(&self, index: usize) -> Light {
+    self.values.get(index).copied().unwrap_or(self.uniform)
+  }
+
+  /**
+   * Changes the light of a voxel, the light of every voxel is stored separately from the first change
+   * `volume`: the amount of voxels in the chunk
+   */
+  pub fn set(&mut self, index: usize, volume: usize, light: Light) {
+    if self.values.is_empty() {
+      if light == self.uniform {
+        return;
+      }
+      self.values = vec![self.uniform; volume];
+    }
+
+    self.values[index] = light;
+  }
+
+  /**
+   * Gives the same light to every voxel, freeing the memory of the voxels
+   */
+  pub fn fill(&mut self, light: Light) {
+    self.uniform = light;
+    self.values = Vec::new();
+  }
+
+  /**
+   * Frees the memory of the voxels if they all have the same light
+   */
+  pub fn compact(&mut self) {
+    if let Some(first) = self.values.first().copied() {
+      if self.values.iter().all(|light| *light == first) {
+        self.fill(first);
+      }
+    }
+  }
+
+  pub fn memory_usage(&self) -> usize {
+    self.values.capacity() * std::mem::size_of::<Light>()
+  }
+}
+
+impl Default for LightField {
+  /**
+   * Chunks that have not been lit yet are treated as if they were in the open sky
+   */
+  fn default() -> Self {
+    Self::new(Light::SKY)
+  }
+}
+
+/**
+ * Returns the light a face receives, the light of the voxel in front of it
+ * `face`: the voxel the face belongs to
+ * `direction`: the direction of the face (0 = right, 1 = left, 2 = up, 3 = down, 4 = front, 5 = back)
+ * `chunk`: the chunk the face is in, its border is used for the faces on the edge of the chunk
+ */
+pub fn face_light(face: &(usize, usize, usize), direction: usize, chunk: &PaddedChunk) -> Light {
+  let front = direction_offset(direction);
+  chunk.light(
+    face.0 as i32 + front.x,
+    face.1 as i32 + front.y,
+    face.2 as i32 + front.z,
+  )
+}
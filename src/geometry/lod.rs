@@ -0,0 +1,205 @@
+use bevy::prelude::*;
+
+use super::chunk::Chunk;
+use super::light::Light;
+use super::padded::PaddedChunk;
+use crate::block::{blocks, BlockId, BlockRegistry};
+
+/**
+ * The lowest level of detail, where every voxel of the mesh stands for 8x8x8 voxels of the chunk
+ */
+pub const MAX_LOD: u32 = 3;
+
+/**
+ * Returns how many voxels of the chunk are merged along each axis at a level of detail
+ */
+pub fn lod_scale(lod: u32) -> usize {
+  1 << lod
+}
+
+/**
+ * Returns the block found the most times in `voxels`, ties go to visible blocks so thin surfaces don't disappear
+ * `registry`: the registry used to know which blocks are visible
+ */
+pub fn majority_block(
+  voxels: impl IntoIterator<Item = BlockId>,
+  registry: &BlockRegistry,
+) -> BlockId {
+  let mut counts: Vec<(BlockId, usize)> = Vec::new();
+  for voxel in voxels {
+    match counts.iter_mut().find(|(block, _)| *block == voxel) {
+      Some((_, count)) => *count += 1,
+      None => counts.push((voxel, 1)),
+    }
+  }
+
+  counts
+    .into_iter()
+    .max_by_key(|(block, count)| (*count, registry.get(*block).is_visible()))
+    .map_or(blocks::AIR, |(block, _)| block)
+}
+
+impl PaddedChunk {
+  /**
+   * Pads a chunk with the voxels of its neighbours at a lower level of detail, every `lod_scale(lod)^3` voxels are merged into one
+   * a merged voxel is made of the block found the most times among the voxels it replaces (see `majority_block`), and gets their brightest light
+   * the result is meshed like any other chunk, its quads have to be scaled by `lod_scale(lod)`
+   * `chunk`, `neighbour`: the chunk in the middle and its neighbours (see `PaddedChunk::from_neighbours`)
+   * `lod`: the level of detail, 0 keeps every voxel
+   * `transitions`: the sides of the chunk (by direction) touching a chunk meshed at another level of detail,
+   *   the border is left empty on those sides so the chunk is closed by faces, which hide the gaps between the two surfaces
+   * `registry`: the registry used to break ties between blocks
+   */
+  pub fn downsampled<'a, F>(
+    chunk: &Chunk,
+    neighbour: F,
+    lod: u32,
+    transitions: [bool; 6],
+    registry: &BlockRegistry,
+  ) -> Self
+  where
+    F: Fn(IVec3) -> Option<&'a Chunk>,
+  {
+    let size = chunk.size as i32;
+    let scale = lod_scale(lod) as i32;
+    debug_assert!(
+      size % scale == 0,
+      "chunks of size {size} can't be merged by {scale}"
+    );
+
+    // the middle chunk and its neighbours, indexed by their offset like the voxels of a chunk of size 3
+    let chunks: Vec<Option<&Chunk>> = (0..27)
+      .map(|i| {
+        let offset = IVec3::new(i / 9, i / 3 % 3, i % 3) - IVec3::ONE;
+        if offset == IVec3::ZERO {
+          Some(chunk)
+        } else {
+          neighbour(offset)
+        }
+      })
+      .collect();
+
+    // missing chunks are air in the open sky, like in `PaddedChunk::from_neighbours`
+    let voxel_at = |voxel: IVec3| {
+      let offset = |v: i32| v.div_euclid(size) + 1;
+      let local = |v: i32| v.rem_euclid(size) as usize;
+      match chunks[(offset(voxel.x) * 9 + offset(voxel.y) * 3 + offset(voxel.z)) as usize] {
+        Some(chunk) => {
+          let (x, y, z) = (local(voxel.x), local(voxel.y), local(voxel.z));
+          (chunk.get(x, y, z), chunk.light(x, y, z))
+        }
+        None => (blocks::AIR, Light::SKY),
+      }
+    };
+
+    let lod_size = size / scale;
+    PaddedChunk::from_fn(lod_size as usize, chunk.uniform_block(), |x, y, z| {
+      let cell = IVec3::new(x, y, z);
+      let (mut sky, mut emitted) = (0, 0);
+      let cell_voxels = (0..scale).flat_map(|dx| {
+        (0..scale).flat_map(move |dy| (0..scale).map(move |dz| IVec3::new(dx, dy, dz)))
+      });
+
+      let block = majority_block(
+        cell_voxels.map(|voxel| {
+          let (block, light) = voxel_at(cell * scale + voxel);
+          sky = sky.max(light.sky());
+          emitted = emitted.max(light.block());
+          block
+        }),
+        registry,
+      );
+
+      // the directions are 0 = right, 1 = left, 2 = up, 3 = down, 4 = front, 5 = back
+      let transition = (0..3).any(|axis| {
+        (cell[axis] == lod_size && transitions[axis * 2])
+          || (cell[axis] == -1 && transitions[axis * 2 + 1])
+      });
+      let block = if transition { blocks::AIR } else { block };
+
+      (block, Light::new(sky, emitted))
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::geometry::mesher::{GreedyMesher, Mesher};
+
+  #[test]
+  fn merged_voxels_take_the_most_common_block() {
+    let registry = BlockRegistry::default();
+    assert_eq!(
+      majority_block(
+        [blocks::STONE, blocks::DIRT, blocks::DIRT, blocks::AIR],
+        &registry
+      ),
+      blocks::DIRT
+    );
+    assert_eq!(
+      majority_block([blocks::AIR, blocks::STONE], &registry),
+      blocks::STONE
+    );
+    assert_eq!(majority_block([], &registry), blocks::AIR);
+
+    // a floor 3 voxels thick, merged 2x2x2 and 4x4x4
+    let mut chunk = Chunk::new(8);
+    for x in 0..8 {
+      for z in 0..8 {
+        for y in 0..3 {
+          chunk.set(x, y, z, blocks::STONE);
+        }
+      }
+    }
+    chunk.set(0, 0, 0, blocks::DIRT);
+
+    let half = PaddedChunk::downsampled(&chunk, |_| None, 1, [false; 6], &registry);
+    assert_eq!(half.size, 4);
+    assert_eq!(half.get(0, 0, 0), blocks::STONE);
+    assert_eq!(half.get(3, 1, 3), blocks::STONE);
+    assert_eq!(half.get(2, 2, 2), blocks::AIR);
+
+    let quarter = PaddedChunk::downsampled(&chunk, |_| None, 2, [false; 6], &registry);
+    assert_eq!(quarter.size, 2);
+    assert_eq!(quarter.get(1, 0, 1), blocks::STONE);
+    assert_eq!(quarter.get(1, 1, 1), blocks::AIR);
+  }
+
+  #[test]
+  fn full_detail_matches_the_padded_chunk() {
+    let registry = BlockRegistry::default();
+    let chunk = Chunk::random(8, &[blocks::AIR, blocks::STONE, blocks::GLASS]);
+    let neighbour = Chunk::random(8, &[blocks::AIR, blocks::DIRT]);
+    let neighbours = |offset: IVec3| (offset.y == 0).then_some(&neighbour);
+
+    let padded = PaddedChunk::from_neighbours(&chunk, neighbours);
+    let downsampled = PaddedChunk::downsampled(&chunk, neighbours, 0, [false; 6], &registry);
+    for x in -1..=8 {
+      for y in -1..=8 {
+        for z in -1..=8 {
+          assert_eq!(padded.get(x, y, z), downsampled.get(x, y, z));
+        }
+      }
+    }
+  }
+
+  #[test]
+  fn transition_sides_are_closed() {
+    let registry = BlockRegistry::default();
+    let stone = Chunk::filled(8, blocks::STONE);
+    let neighbours = |_| Some(&stone);
+
+    // surrounded by stone, a stone chunk has no visible faces
+    let closed = PaddedChunk::downsampled(&stone, neighbours, 1, [false; 6], &registry);
+    assert!(GreedyMesher.quads(&closed, &registry).is_empty());
+
+    // unless its neighbour on the right is meshed at another level of detail
+    let mut transitions = [false; 6];
+    transitions[0] = true;
+    let open = PaddedChunk::downsampled(&stone, neighbours, 1, transitions, &registry);
+    let quads = GreedyMesher.quads(&open, &registry);
+    assert_eq!(quads.len(), 1);
+    assert_eq!(quads[0].direction, 0);
+  }
+}
@@ -0,0 +1,134 @@
+use std::sync::Arc;
+
+use bevy::prelude::*;
+
+use super::binarymesh::binary_quads;
+use super::greedymesh::greedy_quads;
+use super::naivemesh::face_quads;
+use super::padded::PaddedChunk;
+use super::quad::{ChunkMeshes, Quad};
+use super::uniformmesh::uniform_quads;
+use crate::block::BlockRegistry;
+
+/**
+ * Turns the voxels of a chunk into quads
+ * every implementation must produce the same visible surface, they only differ in how many quads they use and how fast they are
+ */
+pub trait Mesher: Send + Sync {
+  /**
+   * A short name for the mesher, used in logs
+   */
+  fn name(&self) -> &'static str;
+
+  /**
+   * Returns the quads of a chunk
+   * `chunk`: the chunk to mesh, its border is only used to cull faces
+   * `registry`: the registry used to decide which faces are visible
+   */
+  fn quads(&self, chunk: &PaddedChunk, registry: &BlockRegistry) -> Vec<Quad>;
+
+  /**
+   * Returns the opaque and transparent meshes of a chunk (see `RenderLayer`)
+   * the smooth blocks don't have quads, their surface is added to the opaque mesh (see `surface_nets`)
+   */
+  fn mesh(&self, chunk: &PaddedChunk, registry: &BlockRegistry) -> ChunkMeshes {
+    ChunkMeshes::from_chunk(&self.quads(chunk, registry), chunk, registry)
+  }
+}
+
+/**
+ * One quad for every face of every visible voxel, including the ones between two voxels
+ * the slowest and heaviest mesher, useful to tell culling bugs apart from merging bugs
+ */
+#[derive(Clone, Copy, Default, Debug)]
+pub struct NaiveMesher;
+
+impl Mesher for NaiveMesher {
+  fn name(&self) -> &'static str {
+    "naive"
+  }
+
+  fn quads(&self, chunk: &PaddedChunk, registry: &BlockRegistry) -> Vec<Quad> {
+    face_quads(chunk, false, registry)
+  }
+}
+
+/**
+ * One quad for every visible face, faces hidden by other voxels are culled but never merged
+ */
+#[derive(Clone, Copy, Default, Debug)]
+pub struct CulledMesher;
+
+impl Mesher for CulledMesher {
+  fn name(&self) -> &'static str {
+    "culled"
+  }
+
+  fn quads(&self, chunk: &PaddedChunk, registry: &BlockRegistry) -> Vec<Quad> {
+    face_quads(chunk, true, registry)
+  }
+}
+
+/**
+ * Merges the visible faces with the same key into rectangles, one face at a time (see `greedy_quads`)
+ * chunks made of a single block skip straight to their sides (see `uniform_quads`)
+ */
+#[derive(Clone, Copy, Default, Debug)]
+pub struct GreedyMesher;
+
+impl Mesher for GreedyMesher {
+  fn name(&self) -> &'static str {
+    "greedy"
+  }
+
+  fn quads(&self, chunk: &PaddedChunk, registry: &BlockRegistry) -> Vec<Quad> {
+    uniform_quads(chunk, registry).unwrap_or_else(|| greedy_quads(chunk, registry))
+  }
+}
+
+/**
+ * Produces the same quads as `GreedyMesher` working on whole rows of faces with bitwise operations (see `binary_quads`)
+ * faster, but limited to chunks of size 64
+ */
+#[derive(Clone, Copy, Default, Debug)]
+pub struct BinaryGreedyMesher;
+
+impl Mesher for BinaryGreedyMesher {
+  fn name(&self) -> &'static str {
+    "binary greedy"
+  }
+
+  fn quads(&self, chunk: &PaddedChunk, registry: &BlockRegistry) -> Vec<Quad> {
+    uniform_quads(chunk, registry).unwrap_or_else(|| binary_quads(chunk, registry))
+  }
+}
+
+/**
+ * The mesher used to mesh the chunks of the world
+ */
+#[derive(Resource, Clone)]
+pub struct ChunkMesher(pub Arc<dyn Mesher>);
+
+impl ChunkMesher {
+  pub fn new(mesher: impl Mesher + 'static) -> Self {
+    Self(Arc::new(mesher))
+  }
+
+  /**
+   * Every mesher, from the simplest to the fastest
+   */
+  pub fn all() -> Vec<Self> {
+    vec![
+      Self::new(NaiveMesher),
+      Self::new(CulledMesher),
+      Self::new(GreedyMesher),
+      Self::new(BinaryGreedyMesher),
+    ]
+  }
+}
+
+impl Default for ChunkMesher {
+  fn default() -> Self {
+    Self::new(BinaryGreedyMesher)
+  }
+}
@@ -1,2 +1,14 @@
-// pub mod voxel;
-pub mod greedymesh;
\ No newline at end of file
+pub mod ao;
+pub mod binarymesh;
+pub mod chunk;
+pub mod collision;
+pub mod greedymesh;
+pub mod light;
+pub mod lod;
+pub mod mesher;
+pub mod naivemesh;
+pub mod padded;
+pub mod quad;
+pub mod raycast;
+pub mod surfacenets;
+pub mod uniformmesh;
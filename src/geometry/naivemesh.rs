@@ -0,0 +1,42 @@
+use super::greedymesh::face_visible;
+use super::padded::PaddedChunk;
+use super::quad::{FaceKey, Quad};
+use crate::block::BlockRegistry;
+
+/**
+ * Returns one quad per face of the visible voxels of a chunk
+ * faces are never merged, and with `cull` set to false they are not even culled (so faces between two voxels are kept)
+ * `chunk`: the chunk to mesh, its border is only used to cull faces
+ * `cull`: wether to skip the faces hidden by another voxel (see `BlockRegistry::face_hidden`)
+ * `registry`: the registry used to decide which faces are visible
+ */
+pub fn face_quads(chunk: &PaddedChunk, cull: bool, registry: &BlockRegistry) -> Vec<Quad> {
+  let size = chunk.size;
+  let mut quads = Vec::new();
+
+  // in the same order as the quads of the greedy mesher: direction by direction, then voxel by voxel
+  for d in 0..6 {
+    for x in 0..size {
+      for y in 0..size {
+        for z in 0..size {
+          let face = (x, y, z);
+          let block = chunk.get(x as i32, y as i32, z as i32);
+
+          if !registry.get(block).has_faces() || (cull && !face_visible(&face, d, chunk, registry)) {
+            continue;
+          }
+
+          quads.push(Quad {
+            position: face,
+            direction: d,
+            width: 1,
+            height: 1,
+            key: FaceKey::new(&face, d, chunk, registry),
+          });
+        }
+      }
+    }
+  }
+
+  quads
+}
@@ -0,0 +1,281 @@
+use bevy::prelude::*;
+
+use super::chunk::Chunk;
+use super::light::{Light, LightField};
+use crate::block::{blocks, BlockId};
+
+/**
+ * Iterates over the offsets of the 26 chunks around a chunk
+ */
+pub fn neighbour_offsets() -> impl Iterator<Item = IVec3> {
+  (-1..=1)
+    .flat_map(|x| (-1..=1).flat_map(move |y| (-1..=1).map(move |z| IVec3::new(x, y, z))))
+    .filter(|offset| *offset != IVec3::ZERO)
+}
+
+/**
+ * Returns the position of a voxel of the border in the sides stored by uniform padded chunks, or `None` for the voxels inside the border
+ * the sides are stored in the order of the directions (0 = right, 1 = left, 2 = up, 3 = down, 4 = front, 5 = back),
+ * the voxels on the edges and corners of the border are stored in the side of their `x`, then of their `y`
+ */
+#[inline]
+fn border_index(size: usize, x: i32, y: i32, z: i32) -> Option<usize> {
+  let (last, padded_size) = (size as i32, size + 2);
+  let (side, u, v) = match (x, y, z) {
+    (-1, _, _) => (1, y, z),
+    (x, _, _) if x == last => (0, y, z),
+    (_, -1, _) => (3, x, z),
+    (_, y, _) if y == last => (2, x, z),
+    (_, _, -1) => (5, x, y),
+    (_, _, z) if z == last => (4, x, y),
+    _ => return None,
+  };
+
+  Some((side * padded_size + (u + 1) as usize) * padded_size + (v + 1) as usize)
+}
+
+/**
+ * The voxels of a padded chunk and their light
+ * `Dense`: every voxel of the chunk and of its border, in the layout of `PaddedChunk::index`
+ * `Uniform`: for chunks made of a single `block`, only the voxels of the border are stored, one layer of `(size + 2)^2` voxels per side
+ *   (see `border_index`), the light inside the border is stored like the one of the chunk (see `LightField`)
+ */
+#[derive(Clone)]
+enum PaddedVoxels {
+  Dense {
+    voxels: Vec<BlockId>,
+    light: Vec<Light>,
+  },
+  Uniform {
+    block: BlockId,
+    border: Vec<BlockId>,
+    border_light: Vec<Light>,
+    light: LightField,
+  },
+}
+
+/**
+ * A copy of a chunk surrounded by a one voxel thick border taken from the neighbouring chunks
+ * this is what the meshers work on, so that faces touching voxels of other chunks can be culled
+ * uniform chunks only store their border, so copying them doesn't depend on the volume of the chunk
+ * `size`: the size of the chunk inside the border
+ * `voxels`: the voxels of the chunk and its border, along with their light
+ */
+#[derive(Clone)]
+pub struct PaddedChunk {
+  pub size: usize,
+  voxels: PaddedVoxels,
+}
+
+impl PaddedChunk {
+  /**
+   * Returns a padded chunk of air in the open sky, with the storage its voxels need
+   * `light`: the light inside the border of a uniform chunk
+   */
+  fn new(size: usize, uniform: Option<BlockId>, light: LightField) -> Self {
+    let padded_size = size + 2;
+    let voxels = match uniform {
+      Some(block) => PaddedVoxels::Uniform {
+        block,
+        border: vec![blocks::AIR; 6 * padded_size * padded_size],
+        border_light: vec![Light::SKY; 6 * padded_size * padded_size],
+        light,
+      },
+      None => PaddedVoxels::Dense {
+        voxels: vec![blocks::AIR; padded_size * padded_size * padded_size],
+        light: vec![Light::SKY; padded_size * padded_size * padded_size],
+      },
+    };
+
+    Self { size, voxels }
+  }
+
+  /**
+   * Pads a chunk with a border of air, as if it had no neighbours
+   */
+  pub fn from_chunk(chunk: &Chunk) -> Self {
+    Self::from_neighbours(chunk, |_| None)
+  }
+
+  /**
+   * Pads a chunk with the voxels of its neighbours
+   * `chunk`: the chunk in the middle
+   * `neighbour`: returns the chunk at the given offset from the middle one (each axis is -1, 0 or 1), missing chunks are treated as air in the open sky
+   */
+  pub fn from_neighbours<'a, F>(chunk: &Chunk, neighbour: F) -> Self
+  where
+    F: Fn(IVec3) -> Option<&'a Chunk>,
+  {
+    let size = chunk.size;
+    let mut padded = Self::new(size, chunk.uniform_block(), chunk.light_field().clone());
+
+    // the 26 neighbours (the middle chunk is copied below)
+    let neighbours: Vec<(IVec3, &Chunk)> = neighbour_offsets()
+      .filter_map(|offset| neighbour(offset).map(|chunk| (offset, chunk)))
+      .collect();
+
+    // converts a padded coordinate to a coordinate inside the neighbour
+    let local = |v: i32| v.rem_euclid(size as i32) as usize;
+
+    for (offset, other) in neighbours {
+      debug_assert_eq!(other.size, size, "neighbouring chunks must have the same size");
+
+      // the range of padded coordinates covered by this neighbour, on each axis
+      let range = |o: i32| match o {
+        -1 => -1..0,
+        0 => 0..size as i32,
+        _ => size as i32..size as i32 + 1,
+      };
+
+      for x in range(offset.x) {
+        for y in range(offset.y) {
+          for z in range(offset.z) {
+            let (block, light) = (
+              other.get(local(x), local(y), local(z)),
+              other.light(local(x), local(y), local(z)),
+            );
+            padded.set(x, y, z, block, light);
+          }
+        }
+      }
+    }
+
+    // the inside of uniform chunks is already complete
+    if let PaddedVoxels::Dense { voxels, light } = &mut padded.voxels {
+      let index =
+        |x: usize, y: usize, z: usize| (((x + 1) * (size + 2) + (y + 1)) * (size + 2)) + (z + 1);
+
+      for ((x, y, z), voxel) in chunk.iter() {
+        voxels[index(x, y, z)] = voxel;
+        light[index(x, y, z)] = chunk.light(x, y, z);
+      }
+    }
+
+    padded
+  }
+
+  /**
+   * Builds a padded chunk voxel by voxel
+   * `size`: the size of the chunk inside the border
+   * `uniform`: the block every voxel inside the border is made of, if they are all the same
+   * `voxel`: returns the block and the light of the voxel at the given padded coordinates (see `PaddedChunk::index`)
+   */
+  pub fn from_fn<F>(size: usize, uniform: Option<BlockId>, mut voxel: F) -> Self
+  where
+    F: FnMut(i32, i32, i32) -> (BlockId, Light),
+  {
+    let mut padded = Self::new(size, uniform, LightField::new(Light::default()));
+
+    for x in -1..=size as i32 {
+      for y in -1..=size as i32 {
+        for z in -1..=size as i32 {
+          let (block, light) = voxel(x, y, z);
+          padded.set(x, y, z, block, light);
+        }
+      }
+    }
+
+    if let PaddedVoxels::Uniform { light, .. } = &mut padded.voxels {
+      light.compact();
+    }
+
+    padded
+  }
+
+  /**
+   * Changes a voxel of the chunk or of its border, the block of the voxels inside uniform chunks can't change
+   */
+  fn set(&mut self, x: i32, y: i32, z: i32, block: BlockId, voxel_light: Light) {
+    let (size, index) = (self.size, self.index(x, y, z));
+    match &mut self.voxels {
+      PaddedVoxels::Dense { voxels, light } => {
+        (voxels[index], light[index]) = (block, voxel_light);
+      }
+      PaddedVoxels::Uniform {
+        block: uniform,
+        border,
+        border_light,
+        light,
+      } => match border_index(size, x, y, z) {
+        Some(index) => (border[index], border_light[index]) = (block, voxel_light),
+        None => {
+          debug_assert_eq!(block, *uniform);
+          let index = (x as usize * size + y as usize) * size + z as usize;
+          light.set(index, size * size * size, voxel_light);
+        }
+      },
+    }
+  }
+
+  /**
+   * Returns the position of a voxel inside the buffer
+   * coordinates go from -1 to `size` (included), 0 is the first voxel of the chunk in the middle
+   */
+  #[inline]
+  pub fn index(&self, x: i32, y: i32, z: i32) -> usize {
+    let padded_size = self.size + 2;
+    debug_assert!(
+      [x, y, z].iter().all(|v| *v >= -1 && *v <= self.size as i32),
+      "({}, {}, {}) is outside of the padded chunk",
+      x,
+      y,
+      z
+    );
+    (((x + 1) as usize * padded_size + (y + 1) as usize) * padded_size) + (z + 1) as usize
+  }
+
+  /**
+   * Returns the block every voxel inside the border is made of, or `None` if the chunk has different blocks
+   */
+  #[inline]
+  pub fn uniform_block(&self) -> Option<BlockId> {
+    match self.voxels {
+      PaddedVoxels::Dense { .. } => None,
+      PaddedVoxels::Uniform { block, .. } => Some(block),
+    }
+  }
+
+  /**
+   * Returns wether any voxel of the chunk or of its border matches `predicate`
+   */
+  pub fn any_voxel(&self, mut predicate: impl FnMut(BlockId) -> bool) -> bool {
+    match &self.voxels {
+      PaddedVoxels::Dense { voxels, .. } => voxels.iter().copied().any(predicate),
+      PaddedVoxels::Uniform { block, border, .. } => {
+        predicate(*block) || border.iter().copied().any(predicate)
+      }
+    }
+  }
+
+  /**
+   * Returns a voxel of the chunk or of its border (see `PaddedChunk::index`)
+   */
+  #[inline]
+  pub fn get(&self, x: i32, y: i32, z: i32) -> BlockId {
+    match &self.voxels {
+      PaddedVoxels::Dense { voxels, .. } => voxels[self.index(x, y, z)],
+      PaddedVoxels::Uniform { block, border, .. } => match border_index(self.size, x, y, z) {
+        Some(index) => border[index],
+        None => *block,
+      },
+    }
+  }
+
+  /**
+   * Returns the light of a voxel of the chunk or of its border (see `PaddedChunk::index`)
+   */
+  #[inline]
+  pub fn light(&self, x: i32, y: i32, z: i32) -> Light {
+    match &self.voxels {
+      PaddedVoxels::Dense { light, .. } => light[self.index(x, y, z)],
+      PaddedVoxels::Uniform {
+        border_light,
+        light,
+        ..
+      } => match border_index(self.size, x, y, z) {
+        Some(index) => border_light[index],
+        None => light.get((x as usize * self.size + y as usize) * self.size + z as usize),
+      },
+    }
+  }
+}
@@ -0,0 +1,378 @@
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, MeshVertexAttribute, PrimitiveTopology};
+use bevy::render::render_resource::VertexFormat;
+
+use super::ao::{face_ao, AO_CURVE};
+use super::light::{face_light, light_brightness, Light};
+use super::padded::PaddedChunk;
+use super::surfacenets::surface_nets;
+use crate::block::{BlockId, BlockRegistry, RenderLayer};
+
+/**
+ * Per vertex id of the block that produced the face, lets shaders and exporters tell merged quads apart
+ */
+pub const ATTRIBUTE_BLOCK_ID: MeshVertexAttribute =
+  MeshVertexAttribute::new("Vertex_BlockId", 988_540_917, VertexFormat::Uint32);
+
+/**
+ * Per vertex ambient occlusion, from 0 (fully occluded) to 1 (not occluded)
+ */
+pub const ATTRIBUTE_AO: MeshVertexAttribute =
+  MeshVertexAttribute::new("Vertex_AmbientOcclusion", 988_540_918, VertexFormat::Float32);
+
+/**
+ * Per vertex index of the tile of the texture atlas the face is textured with (see `BlockTextures`)
+ */
+pub const ATTRIBUTE_TILE: MeshVertexAttribute =
+  MeshVertexAttribute::new("Vertex_AtlasTile", 988_540_919, VertexFormat::Uint32);
+
+/**
+ * Per vertex brightness of the sky light and of the block light the face receives, from 0 to 1 (see `light_brightness`)
+ */
+pub const ATTRIBUTE_LIGHT: MeshVertexAttribute =
+  MeshVertexAttribute::new("Vertex_Light", 988_540_920, VertexFormat::Float32x2);
+
+/**
+ * The attributes of a face, two faces can only be merged into the same quad if their keys are equal
+ * `block`: the block the face belongs to
+ * `ao`: the ambient occlusion of each corner of the face (see `face_ao`)
+ * `light`: the light the face receives (see `face_light`)
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct FaceKey {
+  pub block: BlockId,
+  pub ao: [u8; 4],
+  pub light: Light,
+}
+
+impl FaceKey {
+  /**
+   * Returns the key of a face of a chunk
+   * `face`: the voxel the face belongs to
+   * `direction`: the direction of the face (0 = right, 1 = left, 2 = up, 3 = down, 4 = front, 5 = back)
+   * `chunk`: the chunk the face is in
+   * `registry`: the registry used to know which voxels occlude the face
+   */
+  pub fn new(
+    face: &(usize, usize, usize),
+    direction: usize,
+    chunk: &PaddedChunk,
+    registry: &BlockRegistry,
+  ) -> Self {
+    Self {
+      block: chunk.get(face.0 as i32, face.1 as i32, face.2 as i32),
+      ao: face_ao(face, direction, chunk, registry),
+      light: face_light(face, direction, chunk),
+    }
+  }
+}
+
+/**
+ * A rectangle of faces produced by a mesher
+ * `position`: the voxel the quad starts from (its corner with the lowest coordinates)
+ * `direction`: the direction the quad is facing (0 = right, 1 = left, 2 = up, 3 = down, 4 = front, 5 = back)
+ * `width`: the amount of faces the quad spans in the width direction (see `expand_direction`)
+ * `height`: the amount of faces the quad spans in the height direction (see `expand_direction`)
+ * `key`: the attributes shared by every face of the quad
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct Quad {
+  pub position: (usize, usize, usize),
+  pub direction: usize,
+  pub width: usize,
+  pub height: usize,
+  pub key: FaceKey,
+}
+
+pub struct ExpandDirection {
+  pub width: [usize; 3],
+  pub height: [usize; 3],
+}
+
+/**
+ * Returns the expansion direction for a given direction
+ * `direction`: the direction to expand (0 = right, 1 = left, 2 = up, 3 = down, 4 = front, 5 = back)
+ * returns an `ExpandDirection` struct, containing the expansion directions for the width and height
+ * an `ExpandDirection` struct contains 3 values, one for each axis (x, y, z), a face should be expanded in the axis with a value of 1, and not expanded in the axis with a value of 0
+ */
+pub fn expand_direction(direction: usize) -> ExpandDirection {
+  match direction {
+    // left - right
+    0 | 1 => ExpandDirection {
+      width: [0, 0, 1],
+      height: [0, 1, 0],
+    },
+    // up - down
+    2 | 3 => ExpandDirection {
+      width: [1, 0, 0],
+      height: [0, 0, 1],
+    },
+    // front - back
+    4 | 5 => ExpandDirection {
+      width: [1, 0, 0],
+      height: [0, 1, 0],
+    },
+
+    _ => panic!("Invalid direction!"),
+  }
+}
+
+/**
+ * Returns the normal of a face facing `direction`
+ */
+pub fn direction_normal(direction: usize) -> [f32; 3] {
+  match direction {
+    0 => [1.0, 0.0, 0.0],
+    1 => [-1.0, 0.0, 0.0],
+    2 => [0.0, 1.0, 0.0],
+    3 => [0.0, -1.0, 0.0],
+    4 => [0.0, 0.0, 1.0],
+    5 => [0.0, 0.0, -1.0],
+    _ => panic!("Invalid direction!"),
+  }
+}
+
+/**
+ * Returns the offset to the voxel a face facing `direction` is looking at
+ */
+pub fn direction_offset(direction: usize) -> IVec3 {
+  let normal = direction_normal(direction);
+  IVec3::new(normal[0] as i32, normal[1] as i32, normal[2] as i32)
+}
+
+/**
+ * A vertex of a mesh that isn't made of quads (see `surface_nets`)
+ * `position`: where the vertex is, in voxels from the corner of the chunk
+ * `normal`: the normal of the surface at the vertex
+ * `block`: the block the vertex is colored and textured with
+ * `light`: the light the vertex receives
+ */
+#[derive(Clone, Copy, Debug)]
+pub struct MeshVertex {
+  pub position: Vec3,
+  pub normal: Vec3,
+  pub block: BlockId,
+  pub light: Light,
+}
+
+/**
+ * Accumulates quads and turns them into a `Mesh`
+ */
+#[derive(Default)]
+pub struct QuadMeshBuilder {
+  positions: Vec<[f32; 3]>,
+  normals: Vec<[f32; 3]>,
+  uvs: Vec<[f32; 2]>,
+  colors: Vec<[f32; 4]>,
+  ao: Vec<f32>,
+  light: Vec<[f32; 2]>,
+  block_ids: Vec<u32>,
+  tiles: Vec<u32>,
+  indices: Vec<u32>,
+}
+
+impl QuadMeshBuilder {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /**
+   * Adds the 4 vertices and 2 triangles of a quad
+   * the uvs go from 0 to the size of the quad, so that textures repeat once per voxel instead of stretching
+   * `quad`: the quad to add
+   * `registry`: the registry used to color and texture the quad
+   */
+  pub fn push_quad(&mut self, quad: &Quad, registry: &BlockRegistry) {
+    let expand = expand_direction(quad.direction);
+    let d = quad.direction;
+
+    // faces looking towards a positive axis sit on the far side of their voxel
+    let origin = [
+      quad.position.0 as f32 + if d == 0 { 1.0 } else { 0.0 },
+      quad.position.1 as f32 + if d == 2 { 1.0 } else { 0.0 },
+      quad.position.2 as f32 + if d == 4 { 1.0 } else { 0.0 },
+    ];
+
+    let corner = |w: usize, h: usize| {
+      [
+        origin[0] + (expand.width[0] * w + expand.height[0] * h) as f32,
+        origin[1] + (expand.width[1] * w + expand.height[1] * h) as f32,
+        origin[2] + (expand.width[2] * w + expand.height[2] * h) as f32,
+      ]
+    };
+
+    let base = self.positions.len() as u32;
+
+    self.positions.push(corner(0, 0));
+    self.positions.push(corner(quad.width, 0));
+    self.positions.push(corner(quad.width, quad.height));
+    self.positions.push(corner(0, quad.height));
+
+    let (width, height) = (quad.width as f32, quad.height as f32);
+    self.uvs.extend([[0.0, 0.0], [width, 0.0], [width, height], [0.0, height]]);
+
+    let ao = quad.key.ao;
+
+    // the quad is split along the diagonal with the brightest corners, otherwise the occlusion of a single
+    // corner would bleed into both triangles and the shading would depend on the orientation of the quad
+    let mut idx = if ao[0] as u32 + ao[2] as u32 >= ao[1] as u32 + ao[3] as u32 {
+      [0, 1, 2, 2, 3, 0]
+    } else {
+      [0, 1, 3, 1, 2, 3]
+    };
+
+    // the winding order has to be flipped for these directions, so the triangles face outwards
+    if d == 0 || d == 2 || d == 5 {
+      idx.reverse();
+    }
+
+    self.indices.extend(idx.iter().map(|i| i + base));
+
+    let block = registry.get(quad.key.block);
+    let color = block.color.as_rgba_f32();
+
+    self.normals.extend([direction_normal(d); 4]);
+    self.block_ids.extend([quad.key.block as u32; 4]);
+    self.tiles.extend([block.textures.tile(d); 4]);
+
+    // the colors are lit by the brightest of the two lights, for renderers that don't read the light attribute
+    let sky = light_brightness(quad.key.light.sky());
+    let emitted = light_brightness(quad.key.light.block());
+    self.light.extend([[sky, emitted]; 4]);
+
+    for corner_ao in ao {
+      let light = AO_CURVE[corner_ao as usize] * sky.max(emitted);
+      self.ao.push(corner_ao as f32 / 3.0);
+      self.colors.push([color[0] * light, color[1] * light, color[2] * light, color[3]]);
+    }
+  }
+
+  /**
+   * Adds a vertex without any triangle, and returns its index for `push_triangle`
+   * the texture is projected along the axis closest to the normal, with the tile of the face facing that way
+   * `vertex`: the vertex to add
+   * `registry`: the registry used to color and texture the vertex
+   */
+  pub fn push_vertex(&mut self, vertex: &MeshVertex, registry: &BlockRegistry) -> u32 {
+    let index = self.positions.len() as u32;
+    let (position, normal) = (vertex.position, vertex.normal);
+
+    let abs = normal.abs();
+    let axis = if abs.x >= abs.y && abs.x >= abs.z {
+      0
+    } else if abs.y >= abs.z {
+      1
+    } else {
+      2
+    };
+    let direction = axis * 2 + (normal[axis] < 0.0) as usize;
+    // the same uvs as the quads facing `direction`: v goes up along y on the sides
+    let uv = match axis {
+      0 => [position.z, position.y],
+      1 => [position.x, position.z],
+      _ => [position.x, position.y],
+    };
+
+    let block = registry.get(vertex.block);
+    let color = block.color.as_rgba_f32();
+    let sky = light_brightness(vertex.light.sky());
+    let emitted = light_brightness(vertex.light.block());
+    let light = sky.max(emitted);
+
+    self.positions.push(position.to_array());
+    self.normals.push(normal.to_array());
+    self.uvs.push(uv);
+    self.colors.push([color[0] * light, color[1] * light, color[2] * light, color[3]]);
+    self.ao.push(1.0);
+    self.light.push([sky, emitted]);
+    self.block_ids.push(vertex.block as u32);
+    self.tiles.push(block.textures.tile(direction));
+
+    index
+  }
+
+  /**
+   * Adds a triangle between vertices added with `push_vertex`, the vertices go counter clockwise when looking at the front of the triangle
+   */
+  pub fn push_triangle(&mut self, vertices: [u32; 3]) {
+    self.indices.extend(vertices);
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.indices.is_empty()
+  }
+
+  pub fn build(self) -> Mesh {
+    let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+
+    mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, self.positions);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_UV_0, self.uvs);
+    mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, self.colors);
+    mesh.insert_attribute(ATTRIBUTE_AO, self.ao);
+    mesh.insert_attribute(ATTRIBUTE_LIGHT, self.light);
+    mesh.insert_attribute(ATTRIBUTE_BLOCK_ID, self.block_ids);
+    mesh.insert_attribute(ATTRIBUTE_TILE, self.tiles);
+    mesh.set_indices(Some(Indices::U32(self.indices)));
+
+    mesh
+  }
+}
+
+/**
+ * The meshes of a chunk, one per `RenderLayer`
+ */
+pub struct ChunkMeshes {
+  pub opaque: Mesh,
+  pub transparent: Mesh,
+}
+
+impl ChunkMeshes {
+  /**
+   * Splits quads between the opaque and the transparent mesh, depending on the layer of their block
+   */
+  pub fn from_quads<'a>(quads: impl IntoIterator<Item = &'a Quad>, registry: &BlockRegistry) -> Self {
+    let (opaque, transparent) = Self::split_quads(quads, registry);
+
+    Self {
+      opaque: opaque.build(),
+      transparent: transparent.build(),
+    }
+  }
+
+  /**
+   * Like `from_quads`, with the surface of the smooth blocks of the chunk added to the opaque mesh (see `surface_nets`)
+   * `quads`: the quads of the blocky voxels of the chunk
+   * `chunk`: the chunk the quads come from
+   */
+  pub fn from_chunk<'a>(
+    quads: impl IntoIterator<Item = &'a Quad>,
+    chunk: &PaddedChunk,
+    registry: &BlockRegistry,
+  ) -> Self {
+    let (mut opaque, transparent) = Self::split_quads(quads, registry);
+    surface_nets(chunk, registry, &mut opaque);
+
+    Self {
+      opaque: opaque.build(),
+      transparent: transparent.build(),
+    }
+  }
+
+  fn split_quads<'a>(
+    quads: impl IntoIterator<Item = &'a Quad>,
+    registry: &BlockRegistry,
+  ) -> (QuadMeshBuilder, QuadMeshBuilder) {
+    let mut opaque = QuadMeshBuilder::new();
+    let mut transparent = QuadMeshBuilder::new();
+
+    for quad in quads {
+      match registry.get(quad.key.block).layer() {
+        RenderLayer::Opaque => opaque.push_quad(quad, registry),
+        RenderLayer::Transparent => transparent.push_quad(quad, registry),
+      }
+    }
+
+    (opaque, transparent)
+  }
+}
@@ -0,0 +1,210 @@
+use bevy::prelude::*;
+
+use super::chunk::Chunk;
+use crate::block::BlockRegistry;
+
+/**
+ * The voxel hit by a ray
+ * `voxel`: the coordinates of the voxel, the voxel at `v` fills the space from `v` to `v + 1`
+ * `normal`: the normal of the face the ray entered the voxel from, zero if the ray started inside the voxel
+ * `distance`: how far along the ray the voxel was hit
+ */
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RaycastHit {
+  pub voxel: IVec3,
+  pub normal: IVec3,
+  pub distance: f32,
+}
+
+/**
+ * Walks the voxels crossed by a ray in order, and returns the first one for which `hit` is true
+ * uses the DDA algorithm from "A Fast Voxel Traversal Algorithm for Ray Tracing" (Amanatides, Woo), so every crossed voxel is visited exactly once
+ * `origin`: where the ray starts
+ * `direction`: where the ray is going, doesn't need to be normalized
+ * `max_distance`: how far to look for voxels, panics if it is not finite (a ray that hits nothing would never end)
+ * `hit`: returns wether the ray stops at a voxel
+ */
+pub fn raycast(
+  origin: Vec3,
+  direction: Vec3,
+  max_distance: f32,
+  mut hit: impl FnMut(IVec3) -> bool,
+) -> Option<RaycastHit> {
+  assert!(
+    max_distance.is_finite(),
+    "the max distance of a raycast must be finite"
+  );
+  let direction = direction.try_normalize()?;
+  let mut voxel = origin.floor().as_ivec3();
+
+  // the step to the next voxel on each axis, and the distance along the ray to cross a whole voxel
+  let mut step = IVec3::ZERO;
+  let mut delta = Vec3::splat(f32::INFINITY);
+  // the distance along the ray to the next voxel boundary on each axis
+  let mut next = Vec3::splat(f32::INFINITY);
+
+  for axis in 0..3 {
+    if direction[axis] > 0.0 {
+      step[axis] = 1;
+      delta[axis] = 1.0 / direction[axis];
+      next[axis] = (voxel[axis] as f32 + 1.0 - origin[axis]) * delta[axis];
+    } else if direction[axis] < 0.0 {
+      step[axis] = -1;
+      delta[axis] = -1.0 / direction[axis];
+      next[axis] = (origin[axis] - voxel[axis] as f32) * delta[axis];
+    }
+  }
+
+  let mut normal = IVec3::ZERO;
+  let mut distance = 0.0;
+
+  loop {
+    if hit(voxel) {
+      return Some(RaycastHit {
+        voxel,
+        normal,
+        distance,
+      });
+    }
+
+    // cross the closest boundary
+    let axis = if next.x <= next.y && next.x <= next.z {
+      0
+    } else if next.y <= next.z {
+      1
+    } else {
+      2
+    };
+
+    distance = next[axis];
+    if distance > max_distance {
+      return None;
+    }
+
+    voxel[axis] += step[axis];
+    next[axis] += delta[axis];
+    normal = IVec3::ZERO;
+    normal[axis] = -step[axis];
+  }
+}
+
+impl Chunk {
+  /**
+   * Returns the first solid voxel of the chunk hit by a ray (see `raycast`)
+   * `origin`: where the ray starts, relative to the first voxel of the chunk
+   * `direction`: where the ray is going
+   * `max_distance`: how far to look for voxels
+   * `registry`: the registry used to know which voxels are solid
+   */
+  pub fn raycast(
+    &self,
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+    registry: &BlockRegistry,
+  ) -> Option<RaycastHit> {
+    let size = self.size as i32;
+
+    raycast(origin, direction, max_distance, |voxel| {
+      voxel.cmpge(IVec3::ZERO).all()
+        && voxel.cmplt(IVec3::splat(size)).all()
+        && registry
+          .get(self.get(voxel.x as usize, voxel.y as usize, voxel.z as usize))
+          .solid
+    })
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::block::blocks;
+
+  #[test]
+  fn hits_the_first_voxel_along_the_ray() {
+    let wall = |voxel: IVec3| voxel.x == 5;
+
+    let hit = raycast(Vec3::new(0.5, 0.5, 0.5), Vec3::X, 10.0, wall).unwrap();
+    assert_eq!(hit.voxel, IVec3::new(5, 0, 0));
+    assert_eq!(hit.normal, IVec3::NEG_X);
+    assert!((hit.distance - 4.5).abs() < 1e-5);
+
+    // a diagonal ray crosses one boundary at a time
+    let hit = raycast(
+      Vec3::new(0.2, 0.7, -0.3),
+      Vec3::new(1.0, -0.3, 0.4),
+      20.0,
+      wall,
+    )
+    .unwrap();
+    assert_eq!(hit.voxel.x, 5);
+    assert_eq!(hit.normal, IVec3::NEG_X);
+
+    let hit = raycast(Vec3::new(9.5, 2.5, 1.5), Vec3::NEG_X, 10.0, wall).unwrap();
+    assert_eq!(hit.voxel, IVec3::new(5, 2, 1));
+    assert_eq!(hit.normal, IVec3::X);
+    assert!((hit.distance - 3.5).abs() < 1e-5);
+
+    assert_eq!(raycast(Vec3::new(0.5, 0.5, 0.5), Vec3::X, 4.0, wall), None);
+    assert_eq!(
+      raycast(Vec3::new(0.5, 0.5, 0.5), Vec3::Y, 100.0, wall),
+      None
+    );
+    assert_eq!(raycast(Vec3::ZERO, Vec3::ZERO, 100.0, |_| true), None);
+
+    let inside = raycast(Vec3::new(5.5, 0.5, 0.5), Vec3::X, 10.0, wall).unwrap();
+    assert_eq!(inside.normal, IVec3::ZERO);
+    assert_eq!(inside.distance, 0.0);
+  }
+
+  #[test]
+  fn visits_every_crossed_voxel_once() {
+    let mut visited = Vec::new();
+    raycast(
+      Vec3::new(0.5, 0.5, 0.5),
+      Vec3::new(3.0, 2.0, -1.0),
+      8.0,
+      |voxel| {
+        visited.push(voxel);
+        false
+      },
+    );
+
+    for pair in visited.windows(2) {
+      let step = pair[1] - pair[0];
+      assert_eq!(
+        step.abs().dot(IVec3::ONE),
+        1,
+        "{:?} is not next to {:?}",
+        pair[1],
+        pair[0]
+      );
+    }
+    assert_eq!(visited.last(), Some(&IVec3::new(6, 4, -2)));
+  }
+
+  #[test]
+  #[should_panic]
+  fn infinite_distance_panics() {
+    raycast(Vec3::ZERO, Vec3::X, f32::INFINITY, |_| false);
+  }
+
+  #[test]
+  fn chunk_raycast_skips_non_solid_voxels() {
+    let registry = BlockRegistry::default();
+    let mut chunk = Chunk::new(8);
+    chunk.set(3, 1, 1, blocks::WATER);
+    chunk.set(6, 1, 1, blocks::STONE);
+
+    let hit = chunk
+      .raycast(Vec3::new(0.5, 1.5, 1.5), Vec3::X, 10.0, &registry)
+      .unwrap();
+    assert_eq!(hit.voxel, IVec3::new(6, 1, 1));
+
+    // voxels outside of the chunk are never hit
+    assert_eq!(
+      chunk.raycast(Vec3::new(-4.5, 1.5, 1.5), Vec3::NEG_X, 10.0, &registry),
+      None
+    );
+  }
+}
@@ -0,0 +1,246 @@
+use bevy::prelude::*;
+
+use super::light::Light;
+use super::lod::majority_block;
+use super::padded::PaddedChunk;
+use super::quad::{MeshVertex, QuadMeshBuilder};
+use crate::block::{BlockId, BlockRegistry};
+
+/**
+ * The 12 edges of a cell, as pairs of corners (see `corner_offset`)
+ */
+const CELL_EDGES: [(usize, usize); 12] = [
+  (0, 4),
+  (1, 5),
+  (2, 6),
+  (3, 7),
+  (0, 2),
+  (1, 3),
+  (4, 6),
+  (5, 7),
+  (0, 1),
+  (2, 3),
+  (4, 5),
+  (6, 7),
+];
+
+/**
+ * Returns the offset of the `i`th corner of a cell from its first corner
+ */
+fn corner_offset(i: usize) -> IVec3 {
+  IVec3::new((i >> 2 & 1) as i32, (i >> 1 & 1) as i32, (i & 1) as i32)
+}
+
+/**
+ * Meshes the smooth blocks of a chunk (see `BlockProperties::smooth`) as a single smooth surface, using Surface Nets
+ * the voxels are treated as a density field sampled at their centers (opaque voxels are full, the others empty),
+ * every cell between 8 voxel centers crossed by the surface gets a vertex in the middle of the edges the surface crosses,
+ * and every edge between a smooth voxel and an empty one gets a quad joining the vertices of the 4 cells around it
+ * each chunk makes the quads of the edges going from its voxels towards the positive axes, the vertices near the border
+ * are computed from the voxels of the neighbouring chunks, so the surfaces of neighbouring chunks join without seams
+ * the surface between smooth voxels and opaque blocky voxels is not meshed, instead the faces of the blocky voxels
+ * touching smooth ones are kept (see `BlockRegistry::face_hidden`), so they close the gaps where the surface is rounded
+ * `chunk`: the chunk to mesh
+ * `registry`: the registry used to know which voxels are smooth
+ * `mesh`: the mesh the surface is added to
+ */
+pub fn surface_nets(chunk: &PaddedChunk, registry: &BlockRegistry, mesh: &mut QuadMeshBuilder) {
+  // most chunks have no smooth voxel around them, and the edges of a chunk made of a single blocky block never get a quad
+  let smooth_blocks: Vec<bool> = registry
+    .iter()
+    .map(|(_, block)| block.is_smooth())
+    .collect();
+  let blocky = |block: BlockId| registry.get(block).opaque && !smooth_blocks[block as usize];
+  if chunk.uniform_block().is_some_and(blocky)
+    || !chunk.any_voxel(|block| smooth_blocks[block as usize])
+  {
+    return;
+  }
+
+  let size = chunk.size as i32;
+  let solid = |v: IVec3| registry.get(chunk.get(v.x, v.y, v.z)).opaque;
+  let smooth = |v: IVec3| registry.get(chunk.get(v.x, v.y, v.z)).is_smooth();
+
+  // the cells start from the voxels from -1 to size - 1, `cells` holds the index of their vertex once it is made
+  let cells_size = size + 1;
+  let cell_index = |c: IVec3| (((c.x + 1) * cells_size + c.y + 1) * cells_size + c.z + 1) as usize;
+  let mut cells = vec![None; (cells_size * cells_size * cells_size) as usize];
+  let mut vertices: Vec<MeshVertex> = Vec::new();
+  let mut triangles: Vec<[u32; 3]> = Vec::new();
+
+  let mut cell_vertex = |c: IVec3, vertices: &mut Vec<MeshVertex>| -> u32 {
+    if let Some(vertex) = cells[cell_index(c)] {
+      return vertex;
+    }
+
+    let corners: [IVec3; 8] = std::array::from_fn(|i| c + corner_offset(i));
+
+    // the middle of the crossed edges, from the center of the first voxel of the cell
+    let crossings: Vec<Vec3> = CELL_EDGES
+      .iter()
+      .filter(|(a, b)| solid(corners[*a]) != solid(corners[*b]))
+      .map(|(a, b)| (corner_offset(*a) + corner_offset(*b)).as_vec3() / 2.0)
+      .collect();
+    let offset = crossings.iter().sum::<Vec3>() / crossings.len().max(1) as f32;
+
+    // the most common smooth block of the cell, lit by its brightest empty voxel
+    let block = majority_block(
+      corners
+        .iter()
+        .filter(|v| smooth(**v))
+        .map(|v| chunk.get(v.x, v.y, v.z)),
+      registry,
+    );
+    let (sky, emitted) = corners
+      .iter()
+      .filter(|v| !solid(**v))
+      .map(|v| chunk.light(v.x, v.y, v.z))
+      .fold((0, 0), |(sky, emitted), light| {
+        (sky.max(light.sky()), emitted.max(light.block()))
+      });
+
+    let vertex = vertices.len() as u32;
+    vertices.push(MeshVertex {
+      position: c.as_vec3() + Vec3::splat(0.5) + offset,
+      normal: Vec3::ZERO,
+      block,
+      light: Light::new(sky, emitted),
+    });
+    cells[cell_index(c)] = Some(vertex);
+    vertex
+  };
+
+  for x in 0..size {
+    for y in 0..size {
+      for z in 0..size {
+        let voxel = IVec3::new(x, y, z);
+
+        for axis in 0..3 {
+          let mut step = IVec3::ZERO;
+          step[axis] = 1;
+          let next = voxel + step;
+
+          let (here, there) = (solid(voxel), solid(next));
+          if here == there || !smooth(if here { voxel } else { next }) {
+            continue;
+          }
+
+          // the 4 cells around the edge, counter clockwise when looking from the positive side of the axis
+          let (mut u, mut v) = (IVec3::ZERO, IVec3::ZERO);
+          u[(axis + 1) % 3] = 1;
+          v[(axis + 2) % 3] = 1;
+          let mut quad = [voxel - u - v, voxel - v, voxel, voxel - u]
+            .map(|cell| cell_vertex(cell, &mut vertices));
+
+          // the surface faces away from the solid voxel
+          if there {
+            quad.reverse();
+          }
+
+          triangles.push([quad[0], quad[1], quad[2]]);
+          triangles.push([quad[0], quad[2], quad[3]]);
+        }
+      }
+    }
+  }
+
+  // smooth normals: every vertex gets the average normal of its triangles, weighted by their area
+  for triangle in &triangles {
+    let [a, b, c] = triangle.map(|i| vertices[i as usize].position);
+    let normal = (b - a).cross(c - a);
+    for i in triangle {
+      vertices[*i as usize].normal += normal;
+    }
+  }
+
+  let indices: Vec<u32> = vertices
+    .iter()
+    .map(|vertex| {
+      let normal = vertex.normal.normalize_or_zero();
+      mesh.push_vertex(&MeshVertex { normal, ..*vertex }, registry)
+    })
+    .collect();
+
+  for triangle in triangles {
+    mesh.push_triangle(triangle.map(|i| indices[i as usize]));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::block::{blocks, BlockId, BlockProperties};
+  use crate::geometry::chunk::Chunk;
+  use crate::geometry::mesher::{BinaryGreedyMesher, GreedyMesher, Mesher};
+  use bevy::render::mesh::VertexAttributeValues;
+
+  fn smooth_registry() -> (BlockRegistry, BlockId) {
+    let mut registry = BlockRegistry::default();
+    let soil =
+      registry.register(BlockProperties::opaque("soil", Color::rgb(0.4, 0.3, 0.2)).smoothed());
+    (registry, soil)
+  }
+
+  #[test]
+  fn single_voxel_is_a_closed_surface() {
+    let (registry, soil) = smooth_registry();
+    let mut chunk = Chunk::new(4);
+    chunk.set(1, 2, 1, soil);
+
+    let mut builder = QuadMeshBuilder::new();
+    surface_nets(&PaddedChunk::from_chunk(&chunk), &registry, &mut builder);
+    let mesh = builder.build();
+
+    // one vertex for each of the 8 cells around the voxel, and 2 triangles for each of its 6 sides
+    assert_eq!(mesh.count_vertices(), 8);
+    assert_eq!(mesh.indices().unwrap().len(), 6 * 2 * 3);
+
+    let center = Vec3::new(1.5, 2.5, 1.5);
+    let Some(VertexAttributeValues::Float32x3(positions)) =
+      mesh.attribute(Mesh::ATTRIBUTE_POSITION)
+    else {
+      panic!("no positions");
+    };
+    let Some(VertexAttributeValues::Float32x3(normals)) = mesh.attribute(Mesh::ATTRIBUTE_NORMAL)
+    else {
+      panic!("no normals");
+    };
+    for (position, normal) in positions.iter().zip(normals) {
+      let outwards = Vec3::from(*position) - center;
+      assert!(outwards.length() < 1.0);
+      assert!(
+        Vec3::from(*normal).dot(outwards) > 0.0,
+        "{normal:?} points inwards at {position:?}"
+      );
+    }
+
+    // every triangle faces away from the voxel
+    for triangle in mesh.indices().unwrap().iter().collect::<Vec<_>>().chunks(3) {
+      let [a, b, c] = [0, 1, 2].map(|i| Vec3::from(positions[triangle[i]]));
+      assert!((b - a).cross(c - a).dot((a + b + c) / 3.0 - center) > 0.0);
+    }
+  }
+
+  #[test]
+  fn smooth_blocks_have_no_quads_but_keep_blocky_faces() {
+    let (registry, soil) = smooth_registry();
+    let mut chunk = Chunk::new(8);
+    for x in 0..8 {
+      for z in 0..8 {
+        chunk.set(x, 0, z, soil);
+        chunk.set(x, 1, z, if x < 4 { soil } else { blocks::STONE });
+      }
+    }
+    let padded = PaddedChunk::from_chunk(&chunk);
+
+    // only the stone is meshed with quads, its faces touching the soil are kept to close the seam with the surface
+    let quads = GreedyMesher.quads(&padded, &registry);
+    assert!(quads.iter().all(|quad| quad.key.block == blocks::STONE));
+    assert!(quads.iter().any(|quad| quad.direction == 1));
+    assert!(quads.iter().any(|quad| quad.direction == 3));
+    assert_eq!(BinaryGreedyMesher.quads(&padded, &registry), quads);
+
+    let meshes = GreedyMesher.mesh(&padded, &registry);
+    assert!(meshes.opaque.count_vertices() > 4 * quads.len());
+  }
+}
@@ -0,0 +1,170 @@
+use super::greedymesh::face_visible;
+use super::padded::PaddedChunk;
+use super::quad::{FaceKey, Quad};
+use crate::block::BlockRegistry;
+
+/**
+ * Returns the position of the `i`th face of the layer of voxels touching the side of the chunk facing `direction`
+ * faces are numbered in the same order as the voxels of a chunk (see `Chunk::index`)
+ */
+fn side_face(direction: usize, size: usize, i: usize) -> (usize, usize, usize) {
+  let (a, b) = (i / size, i % size);
+  let last = size - 1;
+
+  match direction {
+    0 => (last, a, b),
+    1 => (0, a, b),
+    2 => (a, last, b),
+    3 => (a, 0, b),
+    4 => (a, b, last),
+    5 => (a, b, 0),
+    _ => panic!("Invalid direction!"),
+  }
+}
+
+/**
+ * Merges the faces of a side of the chunk into quads, the same way `greedy_merge` merges the faces of a whole chunk
+ * `mask`: the key of every visible face of the side, in the order of `side_face`, the faces are removed as they are merged
+ * `direction`: the direction the side is facing
+ * `size`: the size of the chunk
+ * `quads`: where the quads are added
+ */
+fn merge_side(mask: &mut [Option<FaceKey>], direction: usize, size: usize, quads: &mut Vec<Quad>) {
+  // the width of the quads (see `expand_direction`) goes along the second coordinate of the faces of the right and left sides
+  let along_second = direction < 2;
+  let index = |w: usize, h: usize| {
+    if along_second {
+      h * size + w
+    } else {
+      w * size + h
+    }
+  };
+
+  for i in 0..size * size {
+    let Some(key) = mask[i] else {
+      continue;
+    };
+    let (w, h) = if along_second {
+      (i % size, i / size)
+    } else {
+      (i / size, i % size)
+    };
+    let queued = |mask: &[Option<FaceKey>], w: usize, h: usize| mask[index(w, h)] == Some(key);
+
+    let mut width = 1;
+    while w + width < size && queued(mask, w + width, h) {
+      width += 1;
+    }
+
+    let mut height = 1;
+    while h + height < size && (0..width).all(|dw| queued(mask, w + dw, h + height)) {
+      height += 1;
+    }
+
+    for dh in 0..height {
+      for dw in 0..width {
+        mask[index(w + dw, h + dh)] = None;
+      }
+    }
+
+    quads.push(Quad {
+      position: side_face(direction, size, i),
+      direction,
+      width,
+      height,
+      key,
+    });
+  }
+}
+
+/**
+ * Meshes a chunk made of a single block without looking at its voxels
+ * the faces between two voxels of the same block are always hidden (see `BlockRegistry::face_hidden`),
+ * so only the faces on the sides of the chunk can be visible, and only if the neighbouring voxels don't hide them
+ * each side is merged on its own, in a layer of `size^2` faces, so the work doesn't grow with the volume of the chunk
+ * the quads are exactly the ones of `greedy_quads`, in the same order
+ * returns `None` if the chunk is not uniform (see `PaddedChunk::uniform_block`)
+ * `chunk`: the chunk to mesh, its border is only used to cull faces
+ * `registry`: the registry used to decide which faces are visible
+ */
+pub fn uniform_quads(chunk: &PaddedChunk, registry: &BlockRegistry) -> Option<Vec<Quad>> {
+  let block = chunk.uniform_block()?;
+  if !registry.get(block).has_faces() {
+    return Some(Vec::new());
+  }
+
+  let size = chunk.size;
+  let mut quads = Vec::new();
+  let mut mask = vec![None; size * size];
+
+  for d in 0..6 {
+    for (i, entry) in mask.iter_mut().enumerate() {
+      let face = side_face(d, size, i);
+      *entry =
+        face_visible(&face, d, chunk, registry).then(|| FaceKey::new(&face, d, chunk, registry));
+    }
+
+    merge_side(&mut mask, d, size, &mut quads);
+  }
+
+  Some(quads)
+}
+
+#[cfg(test)]
+mod tests {
+  use rand::prelude::*;
+
+  use super::*;
+  use crate::block::{blocks, BlockId};
+  use crate::geometry::chunk::Chunk;
+  use crate::geometry::greedymesh::greedy_quads;
+
+  #[test]
+  fn same_quads_as_greedy_mesher() {
+    let registry = BlockRegistry::default();
+    let mut rng = StdRng::seed_from_u64(18);
+    let size = 8;
+
+    let palette = [blocks::AIR, blocks::STONE, blocks::WATER, blocks::GLASS];
+    let random = Chunk::random_with(&mut rng, size, &palette);
+
+    let uniform_blocks: [BlockId; 5] = [
+      blocks::AIR,
+      blocks::STONE,
+      blocks::WATER,
+      blocks::GLASS,
+      blocks::LEAVES,
+    ];
+    let neighbours = [
+      None,
+      Some(Chunk::filled(size, blocks::STONE)),
+      Some(Chunk::filled(size, blocks::WATER)),
+      Some(random.clone()),
+    ];
+
+    for block in uniform_blocks {
+      let chunk = Chunk::filled(size, block);
+      for neighbour in &neighbours {
+        let padded = PaddedChunk::from_neighbours(&chunk, |_| neighbour.as_ref());
+        let quads = uniform_quads(&padded, &registry).expect("the chunk is uniform");
+        assert_eq!(quads, greedy_quads(&padded, &registry));
+      }
+    }
+
+    assert!(uniform_quads(&PaddedChunk::from_chunk(&random), &registry).is_none());
+  }
+
+  #[test]
+  fn meshes_huge_uniform_chunks_from_their_sides() {
+    // neither the padded chunk nor the mesher allocate per voxel, a queue of every face of this chunk would take gigabytes
+    let registry = BlockRegistry::default();
+    let size = 512;
+    let padded = PaddedChunk::from_chunk(&Chunk::filled(size, blocks::STONE));
+
+    let quads = uniform_quads(&padded, &registry).expect("the chunk is uniform");
+    assert_eq!(quads.len(), 6);
+    assert!(quads
+      .iter()
+      .all(|quad| quad.width == size && quad.height == size));
+  }
+}
@@ -0,0 +1,5 @@
+pub mod block;
+pub mod export;
+pub mod geometry;
+pub mod render;
+pub mod world;
@@ -1,63 +1,163 @@
-use geometry::greedymesh::{Chunk, greedy_mesh};
+use voxel::block::{blocks, BlockRegistry};
+use voxel::export::{export_meshes, ExportMesh};
+use voxel::geometry::mesher::ChunkMesher;
+use voxel::render::material::ChunkRenderPlugin;
+use voxel::world::chunkmap::ChunkMap;
+use voxel::world::picking::{VoxelPicker, VoxelPickingPlugin};
+use voxel::world::player::{PlayerController, PlayerControllerPlugin};
+use voxel::world::region::WorldStorage;
+use voxel::world::streaming::{ChunkEntity, ChunkStreamingPlugin, ChunkViewer, CHUNK_MEMORY, LOADED_CHUNKS};
 
-mod geometry;
-
-use bevy::{prelude::*, pbr::wireframe::{WireframePlugin, Wireframe}};
-use smooth_bevy_cameras::{LookTransform, LookTransformBundle, LookTransformPlugin, Smoother, controllers::orbit::{OrbitCameraPlugin, OrbitCameraBundle, OrbitCameraController}};
+use bevy::{prelude::*, pbr::wireframe::WireframePlugin, diagnostic::LogDiagnosticsPlugin, tasks::IoTaskPool};
+use smooth_bevy_cameras::{LookTransform, LookTransformPlugin, controllers::orbit::{OrbitCameraPlugin, OrbitCameraBundle, OrbitCameraController}};
 
 fn main() {
   App::new()
-    .add_plugins(DefaultPlugins)
+    .add_plugins(DefaultPlugins.set(ImagePlugin::default_nearest()))
     // Enables the system that synchronizes your `Transform`s and `LookTransform`s.
     .add_plugin(LookTransformPlugin)
     .add_plugin(WireframePlugin)
     .add_plugin(OrbitCameraPlugin::default())
+    .add_plugin(ChunkRenderPlugin)
+    .insert_resource(WorldStorage::new("saves/world"))
+    .add_plugin(ChunkStreamingPlugin)
+    .add_plugin(VoxelPickingPlugin)
+    .add_plugin(PlayerControllerPlugin)
+    .add_plugin(LogDiagnosticsPlugin::filtered(vec![LOADED_CHUNKS, CHUNK_MEMORY]))
     .add_startup_system(setup)
+    .add_system(cycle_mesher)
+    .add_system(toggle_smooth_terrain)
+    .add_system(toggle_camera)
+    .add_system(export_chunks)
     .run();
 }
 
-fn setup(
-  mut commands: Commands,
-  mut meshes: ResMut<Assets<Mesh>>,
-  mut materials: ResMut<Assets<StandardMaterial>>,
-) {
-  commands.spawn(PointLightBundle {
-    point_light: PointLight {
-      intensity: 15000.0,
-      shadows_enabled: true,
-      range: 150.0,
-      ..default()
-    },
-    transform: Transform::from_xyz(55.0, 55.0, 55.0),
-    ..default()
-  });
-
+fn setup(mut commands: Commands) {
+  // chunks are loaded around the camera (see `ChunkStreamingPlugin`), and picked with the cursor (see `VoxelPickingPlugin`)
   commands
     .spawn(Camera3dBundle::default())
     .insert(OrbitCameraBundle::new(
         OrbitCameraController::default(),
         Vec3::new(45.0, 45.0, 45.0),
-        Vec3::new(16., 16., 16.),
+        Vec3::new(16., 0., 16.),
         Vec3::Y,
-    ));
+    ))
+    .insert((ChunkViewer, VoxelPicker, PlayerController::default()));
+}
+
+/**
+ * Switches to the next mesher when M is pressed and remeshes every chunk, to compare the meshers side by side
+ */
+fn cycle_mesher(
+  keys: Res<Input<KeyCode>>,
+  mut mesher: ResMut<ChunkMesher>,
+  mut chunk_map: ResMut<ChunkMap>,
+) {
+  if !keys.just_pressed(KeyCode::M) {
+    return;
+  }
 
-  let test_chunk = Chunk::new(32);
-  // test_chunk.print();
+  let meshers = ChunkMesher::all();
+  let current = meshers.iter().position(|m| m.0.name() == mesher.0.name()).unwrap_or(0);
+  *mesher = meshers[(current + 1) % meshers.len()].clone();
 
-  // let start_time = std::time::Instant::now();
+  info!("meshing chunks with the {} mesher", mesher.0.name());
+  chunk_map.mark_all_dirty();
+}
 
-  let mesh = greedy_mesh(&test_chunk);
+/**
+ * Switches the terrain blocks between blocky and smooth (see `surface_nets`) when N is pressed and remeshes every chunk
+ */
+fn toggle_smooth_terrain(
+  keys: Res<Input<KeyCode>>,
+  mut registry: ResMut<BlockRegistry>,
+  mut chunk_map: ResMut<ChunkMap>,
+) {
+  if !keys.just_pressed(KeyCode::N) {
+    return;
+  }
 
-  // println!("Time taken: {}ms", start_time.elapsed().as_millis());
+  for block in [blocks::STONE, blocks::DIRT, blocks::GRASS, blocks::SAND] {
+    let properties = registry.get_mut(block);
+    properties.smooth = !properties.smooth;
+  }
+
+  info!("smooth terrain: {}", registry.get(blocks::STONE).smooth);
+  chunk_map.mark_all_dirty();
+}
 
-  commands.spawn((
-    PbrBundle {
-      mesh: meshes.add(mesh),
-      material: materials.add(Color::rgb(0.8, 0.7, 0.6).into()),
-      transform: Transform::from_xyz(0.0, 0.0, 0.0),
-      ..Default::default()
-    },
-    // This enables wireframe drawing on this entity
-    // Wireframe,
-));
-}
\ No newline at end of file
+/**
+ * Switches between the orbit camera and the first person controller when C is pressed
+ * the new controller starts from where the camera is, looking the same way
+ */
+fn toggle_camera(
+  keys: Res<Input<KeyCode>>,
+  mut cameras: Query<(
+    &Transform,
+    &mut LookTransform,
+    &mut OrbitCameraController,
+    &mut PlayerController,
+  )>,
+) {
+  if !keys.just_pressed(KeyCode::C) {
+    return;
+  }
+
+  for (transform, mut look, mut orbit, mut player) in &mut cameras {
+    player.enabled = !player.enabled;
+    orbit.enabled = !player.enabled;
+
+    if player.enabled {
+      player.look_along(transform.forward());
+      player.velocity = Vec3::ZERO;
+    } else {
+      let radius = look.radius();
+      look.eye = transform.translation;
+      look.target = transform.translation + transform.forward() * radius;
+    }
+  }
+}
+
+/**
+ * Exports the meshes of the loaded chunks to `exports/chunks.glb` and `exports/chunks.obj` when X is pressed
+ * the files are written on the `IoTaskPool`, the game keeps running meanwhile
+ */
+fn export_chunks(
+  keys: Res<Input<KeyCode>>,
+  registry: Res<BlockRegistry>,
+  meshes: Res<Assets<Mesh>>,
+  chunks: Query<(&ChunkEntity, Option<&Handle<Mesh>>, &GlobalTransform)>,
+  mesh_handles: Query<&Handle<Mesh>>,
+) {
+  if !keys.just_pressed(KeyCode::X) {
+    return;
+  }
+
+  let mut exports = Vec::new();
+  for (chunk, opaque, transform) in &chunks {
+    let transparent = mesh_handles.get(chunk.transparent).ok();
+    for (layer, handle) in [("opaque", opaque), ("transparent", transparent)] {
+      let Some(mesh) = handle.and_then(|handle| meshes.get(handle)) else {
+        continue;
+      };
+      exports.push(ExportMesh {
+        name: format!("chunk_{}_{}_{}_{layer}", chunk.coord.x, chunk.coord.y, chunk.coord.z),
+        mesh: mesh.clone(),
+        transform: transform.compute_transform(),
+      });
+    }
+  }
+
+  info!("exporting {} chunk meshes", exports.len());
+  let registry = registry.clone();
+  IoTaskPool::get()
+    .spawn(async move {
+      for path in ["exports/chunks.glb", "exports/chunks.obj"] {
+        match export_meshes(path.as_ref(), &exports, &registry) {
+          Ok(()) => info!("exported the chunks to {path}"),
+          Err(error) => error!("failed to export the chunks to {path}: {error}"),
+        }
+      }
+    })
+    .detach();
+}
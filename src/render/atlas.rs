@@ -0,0 +1,98 @@
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::render::texture::ImageSampler;
+
+use crate::block::BlockRegistry;
+
+/**
+ * The texture the faces of the chunks are sampled from, a grid of square tiles
+ * the tile of a face comes from the `BlockTextures` of its block, tiles are numbered left to right, top to bottom
+ * insert this resource before the `ChunkRenderPlugin` starts up to use your own block art,
+ * otherwise a placeholder atlas is generated from the colors of the blocks (see `generate_atlas`)
+ * `image`: the atlas texture, should use nearest sampling to keep the texels sharp
+ * `columns`: the amount of tiles in each row of the atlas
+ * `rows`: the amount of tiles in each column of the atlas
+ */
+#[derive(Resource, Clone, Debug)]
+pub struct BlockAtlas {
+  pub image: Handle<Image>,
+  pub columns: u32,
+  pub rows: u32,
+}
+
+/**
+ * Pixels per side of the tiles of the generated atlas
+ */
+pub const GENERATED_TILE_SIZE: u32 = 16;
+
+/**
+ * Mixes the coordinates of a texel into a pseudo random value in [0, 1)
+ */
+fn texel_noise(tile: u32, x: u32, y: u32) -> f32 {
+  let mut h = (tile.wrapping_mul(0x9E37_79B9) ^ x.wrapping_mul(0x85EB_CA6B) ^ y.wrapping_mul(0xC2B2_AE35)) as u64;
+  h = (h ^ (h >> 15)).wrapping_mul(0x2C1B_3C6D);
+  h ^= h >> 12;
+
+  (h & 0xFFFF) as f32 / 65536.0
+}
+
+/**
+ * Generates a placeholder atlas from the registry, every tile is the color of the first block using it with some noise on top
+ * the tiles of cutout blocks get see-through holes
+ * returns the image and the amount of columns and rows of tiles in it
+ */
+pub fn generate_atlas(registry: &BlockRegistry) -> (Image, u32, u32) {
+  let mut tile_colors: Vec<Option<(Color, bool)>> = Vec::new();
+
+  for (_, block) in registry.iter().filter(|(_, block)| block.is_visible()) {
+    for tile in block.textures.0 {
+      let tile = tile as usize;
+      if tile >= tile_colors.len() {
+        tile_colors.resize(tile + 1, None);
+      }
+      tile_colors[tile].get_or_insert((block.color, block.cutout));
+    }
+  }
+
+  let count = tile_colors.len().max(1) as u32;
+  let columns = (count as f32).sqrt().ceil() as u32;
+  let rows = count.div_ceil(columns);
+
+  let (width, height) = (columns * GENERATED_TILE_SIZE, rows * GENERATED_TILE_SIZE);
+  let mut data = vec![0; (width * height * 4) as usize];
+
+  for (tile, color) in tile_colors.iter().enumerate() {
+    let Some((color, cutout)) = color else { continue };
+    let [r, g, b, a] = color.as_rgba_f32();
+    let tile = tile as u32;
+    let (tile_x, tile_y) = (tile % columns * GENERATED_TILE_SIZE, tile / columns * GENERATED_TILE_SIZE);
+
+    for y in 0..GENERATED_TILE_SIZE {
+      for x in 0..GENERATED_TILE_SIZE {
+        let noise = texel_noise(tile, x, y);
+        let shade = 0.8 + 0.2 * noise;
+        let alpha = if *cutout && noise > 0.7 { 0.0 } else { a };
+        let i = (((tile_y + y) * width + tile_x + x) * 4) as usize;
+
+        data[i] = (r * shade * 255.0) as u8;
+        data[i + 1] = (g * shade * 255.0) as u8;
+        data[i + 2] = (b * shade * 255.0) as u8;
+        data[i + 3] = (alpha * 255.0) as u8;
+      }
+    }
+  }
+
+  let mut image = Image::new(
+    Extent3d {
+      width,
+      height,
+      depth_or_array_layers: 1,
+    },
+    TextureDimension::D2,
+    data,
+    TextureFormat::Rgba8UnormSrgb,
+  );
+  image.sampler_descriptor = ImageSampler::nearest();
+
+  (image, columns, rows)
+}
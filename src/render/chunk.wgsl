@@ -0,0 +1,73 @@
+#import bevy_pbr::mesh_view_bindings
+#import bevy_pbr::mesh_bindings
+
+struct ChunkMaterial {
+  atlas_tiles: vec2<f32>,
+  sun_direction: vec3<f32>,
+  ambient: f32,
+  alpha_cutoff: f32,
+};
+
+@group(1) @binding(0)
+var<uniform> material: ChunkMaterial;
+@group(1) @binding(1)
+var atlas_texture: texture_2d<f32>;
+@group(1) @binding(2)
+var atlas_sampler: sampler;
+
+// the bindings must come before the functions that use them
+#import bevy_pbr::mesh_functions
+
+struct Vertex {
+  @location(0) position: vec3<f32>,
+  @location(1) uv: vec2<f32>,
+  @location(2) normal: vec3<f32>,
+  @location(3) ao: f32,
+  @location(4) tile: u32,
+  @location(5) light: vec2<f32>,
+};
+
+struct VertexOutput {
+  @builtin(position) clip_position: vec4<f32>,
+  @location(0) world_normal: vec3<f32>,
+  @location(1) uv: vec2<f32>,
+  @location(2) ao: f32,
+  @location(3) @interpolate(flat) tile: u32,
+  @location(4) light: vec2<f32>,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+  var out: VertexOutput;
+  out.clip_position = mesh_position_local_to_clip(mesh.model, vec4<f32>(vertex.position, 1.0));
+  out.world_normal = mesh_normal_local_to_world(vertex.normal);
+  out.uv = vertex.uv;
+  out.ao = vertex.ao;
+  out.tile = vertex.tile;
+  out.light = vertex.light;
+  return out;
+}
+
+// same curve as `AO_CURVE`
+fn ao_light(ao: f32) -> f32 {
+  return 0.4 + 0.6 * ao;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+  // the uvs are in voxels, the fractional part is the position inside the tile (v goes down in the texture)
+  let columns = u32(material.atlas_tiles.x);
+  let tile = vec2<f32>(f32(in.tile % columns), f32(in.tile / columns));
+  let local = vec2<f32>(fract(in.uv.x), 1.0 - fract(in.uv.y));
+  let texel = textureSample(atlas_texture, atlas_sampler, (tile + local) / material.atlas_tiles);
+  if texel.a < material.alpha_cutoff {
+    discard;
+  }
+
+  // the sky light is shaded by the direction of the sun, the light of emissive blocks comes from every direction
+  let sun = max(dot(normalize(in.world_normal), -material.sun_direction), 0.0);
+  let sky = in.light.x * (material.ambient + (1.0 - material.ambient) * sun);
+  let light = max(sky, in.light.y) * ao_light(in.ao);
+
+  return vec4<f32>(texel.rgb * light, texel.a);
+}
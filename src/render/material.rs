@@ -0,0 +1,158 @@
+use bevy::asset::load_internal_asset;
+use bevy::pbr::{MaterialPipeline, MaterialPipelineKey};
+use bevy::prelude::*;
+use bevy::reflect::TypeUuid;
+use bevy::render::mesh::MeshVertexBufferLayout;
+use bevy::render::render_resource::{
+  AsBindGroup, RenderPipelineDescriptor, ShaderRef, SpecializedMeshPipelineError,
+};
+
+use super::atlas::{generate_atlas, BlockAtlas};
+use crate::block::BlockRegistry;
+use crate::geometry::quad::{ATTRIBUTE_AO, ATTRIBUTE_LIGHT, ATTRIBUTE_TILE};
+
+pub const CHUNK_SHADER_HANDLE: HandleUntyped =
+  HandleUntyped::weak_from_u64(Shader::TYPE_UUID, 0x6a4f_0c3e_91d2_7b58);
+
+/**
+ * The material of the chunk meshes, samples the tile of each face from the `BlockAtlas`
+ * the uvs of the meshes are in voxels, so the tile is repeated once per voxel across merged quads
+ * `atlas_tiles`: the amount of columns and rows of tiles in the atlas
+ * `sun_direction`: the direction the light of the sun is travelling in
+ * `ambient`: the amount of sky light faces that don't face the sun receive, from 0 to 1
+ * `alpha_cutoff`: texels with a lower alpha are discarded, used by cutout blocks (see `BlockProperties::cutout`)
+ * `atlas`: the atlas texture
+ * `alpha_mode`: `Opaque` for the opaque meshes of the chunks, `Blend` for the transparent ones
+ */
+#[derive(AsBindGroup, TypeUuid, Debug, Clone)]
+#[uuid = "3d6f2a8e-5b1c-4f7a-9e0d-8c2b4a6e1f37"]
+pub struct ChunkMaterial {
+  #[uniform(0)]
+  pub atlas_tiles: Vec2,
+  #[uniform(0)]
+  pub sun_direction: Vec3,
+  #[uniform(0)]
+  pub ambient: f32,
+  #[uniform(0)]
+  pub alpha_cutoff: f32,
+  #[texture(1)]
+  #[sampler(2)]
+  pub atlas: Handle<Image>,
+  pub alpha_mode: AlphaMode,
+}
+
+impl ChunkMaterial {
+  pub fn new(atlas: &BlockAtlas, alpha_mode: AlphaMode) -> Self {
+    Self {
+      atlas_tiles: Vec2::new(atlas.columns as f32, atlas.rows as f32),
+      sun_direction: Vec3::new(-0.4, -1.0, -0.6).normalize(),
+      ambient: 0.45,
+      alpha_cutoff: 0.5,
+      atlas: atlas.image.clone(),
+      alpha_mode,
+    }
+  }
+
+  /**
+   * The material of the opaque meshes, discards the see-through texels of cutout blocks
+   */
+  pub fn opaque(atlas: &BlockAtlas) -> Self {
+    Self::new(atlas, AlphaMode::Opaque)
+  }
+
+  /**
+   * The material of the transparent meshes, blends every texel
+   */
+  pub fn transparent(atlas: &BlockAtlas) -> Self {
+    Self {
+      alpha_cutoff: 0.0,
+      ..Self::new(atlas, AlphaMode::Blend)
+    }
+  }
+}
+
+impl Material for ChunkMaterial {
+  fn vertex_shader() -> ShaderRef {
+    CHUNK_SHADER_HANDLE.typed().into()
+  }
+
+  fn fragment_shader() -> ShaderRef {
+    CHUNK_SHADER_HANDLE.typed().into()
+  }
+
+  // the cutout is done by the fragment shader, `AlphaMode::Mask` would make the prepass use the shaders of `StandardMaterial`
+  fn alpha_mode(&self) -> AlphaMode {
+    self.alpha_mode
+  }
+
+  fn specialize(
+    _pipeline: &MaterialPipeline<Self>,
+    descriptor: &mut RenderPipelineDescriptor,
+    layout: &MeshVertexBufferLayout,
+    _key: MaterialPipelineKey<Self>,
+  ) -> Result<(), SpecializedMeshPipelineError> {
+    // the first locations match the ones of the prepass shaders, which are specialized by this function too
+    let vertex_layout = layout.get_layout(&[
+      Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+      Mesh::ATTRIBUTE_UV_0.at_shader_location(1),
+      Mesh::ATTRIBUTE_NORMAL.at_shader_location(2),
+      ATTRIBUTE_AO.at_shader_location(3),
+      ATTRIBUTE_TILE.at_shader_location(4),
+      ATTRIBUTE_LIGHT.at_shader_location(5),
+    ])?;
+    descriptor.vertex.buffers = vec![vertex_layout];
+
+    Ok(())
+  }
+}
+
+/**
+ * The handles shared by every chunk entity
+ */
+#[derive(Resource)]
+pub struct ChunkMaterials {
+  pub opaque: Handle<ChunkMaterial>,
+  pub transparent: Handle<ChunkMaterial>,
+}
+
+/**
+ * Sets up the `ChunkMaterial` and the `BlockAtlas` it samples from
+ */
+pub struct ChunkRenderPlugin;
+
+impl Plugin for ChunkRenderPlugin {
+  fn build(&self, app: &mut App) {
+    load_internal_asset!(app, CHUNK_SHADER_HANDLE, "chunk.wgsl", Shader::from_wgsl);
+
+    app
+      .add_plugin(MaterialPlugin::<ChunkMaterial>::default())
+      .add_startup_system(setup_chunk_materials);
+  }
+}
+
+fn setup_chunk_materials(
+  mut commands: Commands,
+  registry: Res<BlockRegistry>,
+  atlas: Option<Res<BlockAtlas>>,
+  mut images: ResMut<Assets<Image>>,
+  mut materials: ResMut<Assets<ChunkMaterial>>,
+) {
+  let atlas = match atlas {
+    Some(atlas) => atlas.clone(),
+    None => {
+      let (image, columns, rows) = generate_atlas(&registry);
+      let atlas = BlockAtlas {
+        image: images.add(image),
+        columns,
+        rows,
+      };
+      commands.insert_resource(atlas.clone());
+      atlas
+    }
+  };
+
+  commands.insert_resource(ChunkMaterials {
+    opaque: materials.add(ChunkMaterial::opaque(&atlas)),
+    transparent: materials.add(ChunkMaterial::transparent(&atlas)),
+  });
+}
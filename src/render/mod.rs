@@ -0,0 +1,2 @@
+pub mod atlas;
+pub mod material;
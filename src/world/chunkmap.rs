@@ -0,0 +1,366 @@
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+
+use crate::block::{BlockId, BlockRegistry};
+use crate::geometry::chunk::Chunk;
+use crate::geometry::collision::{sweep, Aabb, Sweep};
+use crate::geometry::light::Light;
+use crate::geometry::padded::{neighbour_offsets, PaddedChunk};
+use crate::geometry::raycast::{raycast, RaycastHit};
+
+/**
+ * The size (in voxels) of a side of the chunks in the world
+ */
+pub const CHUNK_SIZE: usize = 32;
+
+/**
+ * The chunks of the world, keyed by chunk coordinates
+ * the chunk at coordinates `c` contains the voxels from `c * CHUNK_SIZE` to `(c + 1) * CHUNK_SIZE` (excluded)
+ * `chunks`: the loaded chunks
+ * `dirty`: the chunks whose mesh is out of date
+ * `modified`: the chunks that have been edited since they were loaded, and need to be saved (see `WorldStorage`)
+ * `unlit`: the chunks whose light hasn't been computed yet (see `lighting`)
+ * `light_updates`: the voxels that have been edited since the light was last updated
+ */
+#[derive(Resource, Default)]
+pub struct ChunkMap {
+  chunks: HashMap<IVec3, Chunk>,
+  dirty: HashSet<IVec3>,
+  modified: HashSet<IVec3>,
+  unlit: HashSet<IVec3>,
+  light_updates: Vec<IVec3>,
+}
+
+impl ChunkMap {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /**
+   * Returns the coordinates of the chunk containing a voxel
+   * `voxel`: the world coordinates of the voxel
+   */
+  pub fn chunk_coord(voxel: IVec3) -> IVec3 {
+    let size = CHUNK_SIZE as i32;
+    IVec3::new(
+      voxel.x.div_euclid(size),
+      voxel.y.div_euclid(size),
+      voxel.z.div_euclid(size),
+    )
+  }
+
+  /**
+   * Returns the coordinates of a voxel inside of its chunk
+   * `voxel`: the world coordinates of the voxel
+   */
+  pub fn local_coord(voxel: IVec3) -> (usize, usize, usize) {
+    let size = CHUNK_SIZE as i32;
+    (
+      voxel.x.rem_euclid(size) as usize,
+      voxel.y.rem_euclid(size) as usize,
+      voxel.z.rem_euclid(size) as usize,
+    )
+  }
+
+  /**
+   * Returns the world position of the first voxel of a chunk
+   */
+  pub fn chunk_origin(coord: IVec3) -> IVec3 {
+    coord * CHUNK_SIZE as i32
+  }
+
+  /**
+   * Adds a chunk to the world, returns the chunk that was previously at the same coordinates
+   * the chunk and its loaded neighbours (whose borders changed) are marked as dirty, and the chunk as unlit
+   */
+  pub fn insert(&mut self, coord: IVec3, chunk: Chunk) -> Option<Chunk> {
+    debug_assert_eq!(
+      chunk.size, CHUNK_SIZE,
+      "chunks in the world must be CHUNK_SIZE big"
+    );
+    let previous = self.chunks.insert(coord, chunk);
+    self.unlit.insert(coord);
+    self.mark_dirty(coord);
+    self.mark_neighbours_dirty(coord);
+    previous
+  }
+
+  /**
+   * Removes a chunk from the world, its loaded neighbours are marked as dirty
+   */
+  pub fn remove(&mut self, coord: IVec3) -> Option<Chunk> {
+    self.dirty.remove(&coord);
+    self.modified.remove(&coord);
+    self.unlit.remove(&coord);
+    let chunk = self.chunks.remove(&coord)?;
+    self.mark_neighbours_dirty(coord);
+    Some(chunk)
+  }
+
+  pub fn get(&self, coord: IVec3) -> Option<&Chunk> {
+    self.chunks.get(&coord)
+  }
+
+  pub fn get_mut(&mut self, coord: IVec3) -> Option<&mut Chunk> {
+    self.chunks.get_mut(&coord)
+  }
+
+  pub fn contains(&self, coord: IVec3) -> bool {
+    self.chunks.contains_key(&coord)
+  }
+
+  /**
+   * Returns the amount of bytes used by the voxels of the loaded chunks
+   */
+  pub fn memory_usage(&self) -> usize {
+    self.chunks.values().map(Chunk::memory_usage).sum()
+  }
+
+  pub fn len(&self) -> usize {
+    self.chunks.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.chunks.is_empty()
+  }
+
+  /**
+   * Marks a chunk as needing to be remeshed, does nothing if the chunk is not loaded
+   */
+  pub fn mark_dirty(&mut self, coord: IVec3) {
+    if self.chunks.contains_key(&coord) {
+      self.dirty.insert(coord);
+    }
+  }
+
+  fn mark_neighbours_dirty(&mut self, coord: IVec3) {
+    for offset in neighbour_offsets() {
+      self.mark_dirty(coord + offset);
+    }
+  }
+
+  /**
+   * Marks every loaded chunk as dirty, e.g. after changing how chunks are meshed
+   */
+  pub fn mark_all_dirty(&mut self) {
+    self.dirty.extend(self.chunks.keys().copied());
+  }
+
+  pub fn is_dirty(&self, coord: IVec3) -> bool {
+    self.dirty.contains(&coord)
+  }
+
+  /**
+   * Marks a chunk as up to date (usually after it has been remeshed)
+   */
+  pub fn clear_dirty(&mut self, coord: IVec3) {
+    self.dirty.remove(&coord);
+  }
+
+  /**
+   * Iterates over the coordinates of the chunks that need to be remeshed
+   */
+  pub fn dirty_chunks(&self) -> impl Iterator<Item = IVec3> + '_ {
+    self.dirty.iter().copied()
+  }
+
+  /**
+   * Returns wether a chunk has been edited since it was loaded (or last saved)
+   */
+  pub fn is_modified(&self, coord: IVec3) -> bool {
+    self.modified.contains(&coord)
+  }
+
+  /**
+   * Marks a chunk as needing to be saved, does nothing if the chunk is not loaded
+   */
+  pub fn mark_modified(&mut self, coord: IVec3) {
+    if self.chunks.contains_key(&coord) {
+      self.modified.insert(coord);
+    }
+  }
+
+  /**
+   * Marks a chunk as saved
+   */
+  pub fn clear_modified(&mut self, coord: IVec3) {
+    self.modified.remove(&coord);
+  }
+
+  /**
+   * Iterates over the coordinates of the chunks that need to be saved
+   */
+  pub fn modified_chunks(&self) -> impl Iterator<Item = IVec3> + '_ {
+    self.modified.iter().copied()
+  }
+
+  /**
+   * Returns the chunks that need to be lit, and forgets about them
+   */
+  pub fn take_unlit_chunks(&mut self) -> Vec<IVec3> {
+    self.unlit.drain().collect()
+  }
+
+  /**
+   * Returns the voxels that have been edited since the last call, whose light needs to be updated
+   */
+  pub fn take_light_updates(&mut self) -> Vec<IVec3> {
+    std::mem::take(&mut self.light_updates)
+  }
+
+  /**
+   * Iterates over the loaded chunks, yields `(coordinates, chunk)`
+   */
+  pub fn iter(&self) -> impl Iterator<Item = (IVec3, &Chunk)> {
+    self.chunks.iter().map(|(coord, chunk)| (*coord, chunk))
+  }
+
+  /**
+   * Returns the voxel at the given world coordinates, or `None` if its chunk is not loaded
+   */
+  pub fn get_voxel(&self, voxel: IVec3) -> Option<BlockId> {
+    let (x, y, z) = Self::local_coord(voxel);
+    self
+      .get(Self::chunk_coord(voxel))
+      .map(|chunk| chunk.get(x, y, z))
+  }
+
+  /**
+   * Changes the voxel at the given world coordinates, returns the previous voxel or `None` if its chunk is not loaded
+   * the chunk is marked as dirty and modified, along with the neighbours that have the voxel in their border (see `PaddedChunk`)
+   * the light around the voxel is updated later by the light engine (see `lighting::update_light`)
+   */
+  pub fn set_voxel(&mut self, voxel: IVec3, block: BlockId) -> Option<BlockId> {
+    let coord = Self::chunk_coord(voxel);
+    let (x, y, z) = Self::local_coord(voxel);
+
+    let chunk = self.chunks.get_mut(&coord)?;
+    let previous = chunk.get(x, y, z);
+    if previous == block {
+      return Some(previous);
+    }
+    chunk.set(x, y, z, block);
+    self.modified.insert(coord);
+    self.light_updates.push(voxel);
+    self.mark_voxel_dirty(voxel);
+
+    Some(previous)
+  }
+
+  /**
+   * Returns the light of the voxel at the given world coordinates, or `None` if its chunk is not loaded
+   */
+  pub fn get_light(&self, voxel: IVec3) -> Option<Light> {
+    let (x, y, z) = Self::local_coord(voxel);
+    self
+      .get(Self::chunk_coord(voxel))
+      .map(|chunk| chunk.light(x, y, z))
+  }
+
+  /**
+   * Changes the light of the voxel at the given world coordinates, does nothing if its chunk is not loaded
+   * if the light changed, the chunks that have the voxel in their border are marked as dirty (but not as modified, light is never saved)
+   */
+  pub fn set_light(&mut self, voxel: IVec3, light: Light) {
+    let (x, y, z) = Self::local_coord(voxel);
+    let Some(chunk) = self.chunks.get_mut(&Self::chunk_coord(voxel)) else {
+      return;
+    };
+
+    if chunk.light(x, y, z) != light {
+      chunk.set_light(x, y, z, light);
+      self.mark_voxel_dirty(voxel);
+    }
+  }
+
+  /**
+   * Marks as dirty the chunk of a voxel, along with the neighbours that have the voxel in their border (see `PaddedChunk`)
+   */
+  fn mark_voxel_dirty(&mut self, voxel: IVec3) {
+    let coord = Self::chunk_coord(voxel);
+    let (x, y, z) = Self::local_coord(voxel);
+
+    // the neighbours the voxel is touching, on each axis
+    let touching = |local: usize| match local {
+      0 => -1..=0,
+      l if l == CHUNK_SIZE - 1 => 0..=1,
+      _ => 0..=0,
+    };
+
+    for dx in touching(x) {
+      for dy in touching(y) {
+        for dz in touching(z) {
+          self.mark_dirty(coord + IVec3::new(dx, dy, dz));
+        }
+      }
+    }
+  }
+
+  /**
+   * Returns the first solid voxel of the world hit by a ray (see `raycast`), voxels of chunks that are not loaded are never hit
+   * `origin`: where the ray starts, in world coordinates
+   * `direction`: where the ray is going
+   * `max_distance`: how far to look for voxels
+   * `registry`: the registry used to know which voxels are solid
+   */
+  pub fn raycast(
+    &self,
+    origin: Vec3,
+    direction: Vec3,
+    max_distance: f32,
+    registry: &BlockRegistry,
+  ) -> Option<RaycastHit> {
+    raycast(origin, direction, max_distance, |voxel| {
+      self
+        .get_voxel(voxel)
+        .is_some_and(|block| registry.get(block).solid)
+    })
+  }
+
+  /**
+   * Moves a box through the world without entering solid voxels (see `sweep`)
+   * chunks that are not loaded yet are treated as solid, so nothing falls out of the world while it loads
+   * `aabb`: the box to move, in world coordinates
+   * `motion`: how far the box wants to move
+   * `registry`: the registry used to know which voxels are solid
+   */
+  pub fn sweep(&self, aabb: &Aabb, motion: Vec3, registry: &BlockRegistry) -> Sweep {
+    sweep(aabb, motion, |voxel| {
+      self
+        .get_voxel(voxel)
+        .map_or(true, |block| registry.get(block).solid)
+    })
+  }
+
+  /**
+   * Returns a copy of a chunk padded with the voxels of its neighbours, ready to be meshed
+   * neighbours that are not loaded are treated as air
+   */
+  pub fn padded_chunk(&self, coord: IVec3) -> Option<PaddedChunk> {
+    let chunk = self.get(coord)?;
+    Some(PaddedChunk::from_neighbours(chunk, |offset| {
+      self.get(coord + offset)
+    }))
+  }
+
+  /**
+   * Returns a copy of a chunk padded with the voxels of its neighbours at a lower level of detail (see `PaddedChunk::downsampled`)
+   * `transitions`: the sides of the chunk whose neighbour is meshed at another level of detail
+   */
+  pub fn downsampled_chunk(
+    &self,
+    coord: IVec3,
+    lod: u32,
+    transitions: [bool; 6],
+    registry: &BlockRegistry,
+  ) -> Option<PaddedChunk> {
+    let chunk = self.get(coord)?;
+    Some(PaddedChunk::downsampled(
+      chunk,
+      |offset| self.get(coord + offset),
+      lod,
+      transitions,
+      registry,
+    ))
+  }
+}
@@ -0,0 +1,204 @@
+use std::sync::Arc;
+
+use bevy::prelude::*;
+
+use super::chunkmap::CHUNK_SIZE;
+use super::noise::{fbm_2d, fbm_3d};
+use crate::block::{blocks, BlockId};
+use crate::geometry::chunk::Chunk;
+
+/**
+ * Fills the chunks of the world with voxels
+ * implementations must be deterministic: the same generator and chunk coordinates must always produce the same voxels
+ */
+pub trait TerrainGenerator: Send + Sync {
+  /**
+   * Returns the voxels of the chunk at `coord`
+   * `coord`: the coordinates of the chunk (see `ChunkMap`)
+   * `size`: the size of the chunk
+   */
+  fn generate(&self, coord: IVec3, size: usize) -> Chunk;
+}
+
+/**
+ * The generator used to create the chunks of the world
+ */
+#[derive(Resource, Clone)]
+pub struct WorldGenerator(pub Arc<dyn TerrainGenerator>);
+
+impl WorldGenerator {
+  pub fn new(generator: impl TerrainGenerator + 'static) -> Self {
+    Self(Arc::new(generator))
+  }
+
+  pub fn generate(&self, coord: IVec3) -> Chunk {
+    self.0.generate(coord, CHUNK_SIZE)
+  }
+}
+
+impl Default for WorldGenerator {
+  fn default() -> Self {
+    Self::new(NoiseTerrainGenerator::default())
+  }
+}
+
+/**
+ * Generates terrain from layered gradient noise
+ * a 2d heightmap gives the shape of the surface, a 3d density field carves caves and overhangs into it
+ * `seed`: the seed of every noise layer
+ * `base_height`: the average height of the surface
+ * `height_amplitude`: how far the surface goes above and below `base_height`
+ * `height_scale`: the size (in voxels) of the features of the heightmap
+ * `height_octaves`: the amount of noise layers in the heightmap
+ * `density_scale`: the size (in voxels) of the features of the density field
+ * `density_strength`: how much the density field changes the terrain, 0 disables it
+ * `density_octaves`: the amount of noise layers in the density field
+ * `sea_level`: surfaces below this height are covered in sand instead of grass
+ */
+#[derive(Clone, Debug)]
+pub struct NoiseTerrainGenerator {
+  pub seed: u64,
+  pub base_height: f32,
+  pub height_amplitude: f32,
+  pub height_scale: f32,
+  pub height_octaves: u32,
+  pub density_scale: f32,
+  pub density_strength: f32,
+  pub density_octaves: u32,
+  pub sea_level: i32,
+}
+
+impl Default for NoiseTerrainGenerator {
+  fn default() -> Self {
+    Self {
+      seed: 0,
+      base_height: 16.0,
+      height_amplitude: 24.0,
+      height_scale: 128.0,
+      height_octaves: 4,
+      density_scale: 32.0,
+      density_strength: 12.0,
+      density_octaves: 2,
+      sea_level: 4,
+    }
+  }
+}
+
+impl NoiseTerrainGenerator {
+  pub fn new(seed: u64) -> Self {
+    Self {
+      seed,
+      ..Default::default()
+    }
+  }
+
+  /**
+   * Returns the height of the surface at a column of the world
+   */
+  pub fn height(&self, x: i32, z: i32) -> f32 {
+    let noise = fbm_2d(
+      self.seed,
+      x as f32 / self.height_scale,
+      z as f32 / self.height_scale,
+      self.height_octaves,
+    );
+
+    self.base_height + noise * self.height_amplitude
+  }
+
+  /**
+   * Returns wether the voxel at the given world position is solid
+   * `height`: the height of the surface at the column of the voxel (see `NoiseTerrainGenerator::height`)
+   */
+  pub fn is_solid(&self, x: i32, y: i32, z: i32, height: f32) -> bool {
+    // positive below the surface, negative above it
+    let mut density = height - y as f32;
+
+    if self.density_strength > 0.0 {
+      density += self.density_strength
+        * fbm_3d(
+          self.seed ^ 0xA5A5_A5A5_A5A5_A5A5,
+          x as f32 / self.density_scale,
+          y as f32 / self.density_scale,
+          z as f32 / self.density_scale,
+          self.density_octaves,
+        );
+    }
+
+    density > 0.0
+  }
+
+  /**
+   * Picks the block of a solid voxel
+   * `depth`: the amount of solid voxels between this one and the air above it (0 = this is the surface)
+   */
+  fn surface_block(&self, y: i32, depth: usize) -> BlockId {
+    match depth {
+      0 if y < self.sea_level => blocks::SAND,
+      0 => blocks::GRASS,
+      1..=3 if y < self.sea_level => blocks::SAND,
+      1..=3 => blocks::DIRT,
+      _ => blocks::STONE,
+    }
+  }
+}
+
+impl TerrainGenerator for NoiseTerrainGenerator {
+  fn generate(&self, coord: IVec3, size: usize) -> Chunk {
+    let mut chunk = Chunk::new(size);
+    let origin = coord * size as i32;
+
+    // the voxels above the chunk are needed to know how deep a voxel is, 4 are enough to tell grass, dirt and stone apart
+    const LOOKAHEAD: usize = 4;
+    let mut column = vec![false; size + LOOKAHEAD];
+
+    for x in 0..size {
+      for z in 0..size {
+        let (wx, wz) = (origin.x + x as i32, origin.z + z as i32);
+        let height = self.height(wx, wz);
+
+        // skip columns that are entirely above the surface (the density field is roughly in [-1, 1], with some margin)
+        if (origin.y as f32) > height + self.density_strength * 2.0 {
+          continue;
+        }
+
+        for (y, solid) in column.iter_mut().enumerate() {
+          *solid = self.is_solid(wx, origin.y + y as i32, wz, height);
+        }
+
+        // walk the column from the top, counting how many solid voxels there are above the current one
+        let mut depth = column[size..].iter().take_while(|solid| **solid).count();
+        for y in (0..size).rev() {
+          if !column[y] {
+            depth = 0;
+            continue;
+          }
+
+          chunk.set(x, y, z, self.surface_block(origin.y + y as i32, depth));
+          depth += 1;
+        }
+      }
+    }
+
+    // chunks deep underground end up made only of stone, they don't need any memory for their voxels
+    chunk.compact();
+    chunk
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn same_seed_generates_the_same_voxels() {
+    // the surface is around `base_height`, inside the chunk at the origin
+    let coord = IVec3::ZERO;
+    let voxels = |seed| NoiseTerrainGenerator::new(seed).generate(coord, CHUNK_SIZE).to_voxels();
+
+    assert_eq!(voxels(1), voxels(1));
+    assert_ne!(voxels(1), voxels(2));
+    assert!(voxels(1).contains(&blocks::GRASS));
+    assert!(voxels(1).contains(&blocks::AIR));
+  }
+}
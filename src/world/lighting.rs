@@ -0,0 +1,457 @@
+/*!
+ * The light engine: flood fills the light of the sun and of emissive blocks through the voxels of the world
+ *
+ * every voxel has two light levels from 0 to `MAX_LIGHT` (see `Light`):
+ * - sky light: voxels that see the sky get the full level, which goes down columns of see-through voxels without fading
+ * - block light: emissive blocks get the level they emit (see `BlockProperties::emissive`)
+ *
+ * from there, both levels spread to the neighbouring voxels that are not opaque, losing one level per voxel
+ *
+ * chunks are lit when they are loaded, edits update the light incrementally:
+ * removed light is taken away with a flood fill that follows the decreasing levels, then the voxels around it spread their light back
+ * chunks whose column above isn't loaded are treated as open to the sky, and fixed up once the chunk above them is loaded
+ */
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+
+use super::chunkmap::{ChunkMap, CHUNK_SIZE};
+use crate::block::BlockRegistry;
+use crate::geometry::light::{Light, MAX_LIGHT};
+use crate::geometry::quad::direction_offset;
+
+/**
+ * The direction light goes down in (see `direction_offset`)
+ */
+const DOWN: usize = 3;
+
+/**
+ * One of the two levels of a `Light`
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+enum Channel {
+  Sky,
+  Block,
+}
+
+impl Channel {
+  fn get(self, light: Light) -> u8 {
+    match self {
+      Channel::Sky => light.sky(),
+      Channel::Block => light.block(),
+    }
+  }
+
+  fn with(self, light: Light, level: u8) -> Light {
+    match self {
+      Channel::Sky => light.with_sky(level),
+      Channel::Block => light.with_block(level),
+    }
+  }
+
+  /**
+   * Returns the level a voxel gets from its neighbour in `direction`, if the neighbour has `level`
+   */
+  fn spread(self, level: u8, direction: usize) -> u8 {
+    if self == Channel::Sky && direction == DOWN && level == MAX_LIGHT {
+      MAX_LIGHT
+    } else {
+      level.saturating_sub(1)
+    }
+  }
+}
+
+fn level(map: &ChunkMap, voxel: IVec3, channel: Channel) -> Option<u8> {
+  map.get_light(voxel).map(|light| channel.get(light))
+}
+
+fn set_level(map: &mut ChunkMap, voxel: IVec3, channel: Channel, level: u8) {
+  if let Some(light) = map.get_light(voxel) {
+    map.set_light(voxel, channel.with(light, level));
+  }
+}
+
+/**
+ * Returns wether light can go through a voxel, voxels that are not loaded stop it
+ */
+fn lets_light_through(map: &ChunkMap, voxel: IVec3, registry: &BlockRegistry) -> bool {
+  map
+    .get_voxel(voxel)
+    .is_some_and(|block| !registry.get(block).opaque)
+}
+
+/**
+ * Returns the block light a voxel emits
+ */
+fn emission(map: &ChunkMap, voxel: IVec3, registry: &BlockRegistry) -> u8 {
+  map
+    .get_voxel(voxel)
+    .map_or(0, |block| registry.get(block).emissive.min(MAX_LIGHT))
+}
+
+/**
+ * Spreads the light of the queued voxels to their neighbours, until no voxel gets brighter
+ */
+fn propagate(
+  map: &mut ChunkMap,
+  registry: &BlockRegistry,
+  channel: Channel,
+  mut queue: VecDeque<IVec3>,
+) {
+  while let Some(voxel) = queue.pop_front() {
+    let Some(current) = level(map, voxel, channel) else {
+      continue;
+    };
+
+    for d in 0..6 {
+      let spread = channel.spread(current, d);
+      if spread == 0 {
+        continue;
+      }
+
+      let neighbour = voxel + direction_offset(d);
+      if !lets_light_through(map, neighbour, registry) {
+        continue;
+      }
+
+      if level(map, neighbour, channel).is_some_and(|level| level < spread) {
+        set_level(map, neighbour, channel, spread);
+        queue.push_back(neighbour);
+      }
+    }
+  }
+}
+
+/**
+ * Takes away the light that came from the given voxels, then lets the light around the dark area flow back into it
+ * `removed`: the voxels whose light has been removed (their level is already 0), with the level they had
+ */
+fn unpropagate(
+  map: &mut ChunkMap,
+  registry: &BlockRegistry,
+  channel: Channel,
+  mut removed: VecDeque<(IVec3, u8)>,
+) {
+  let mut relight = VecDeque::new();
+
+  while let Some((voxel, removed_level)) = removed.pop_front() {
+    for d in 0..6 {
+      let neighbour = voxel + direction_offset(d);
+      let Some(neighbour_level) = level(map, neighbour, channel) else {
+        continue;
+      };
+      if neighbour_level == 0 {
+        continue;
+      }
+
+      // a neighbour with less light (or sky light straight from above) got it from this voxel
+      if neighbour_level < removed_level
+        || channel.spread(removed_level, d) == MAX_LIGHT && neighbour_level == MAX_LIGHT
+      {
+        set_level(map, neighbour, channel, 0);
+        removed.push_back((neighbour, neighbour_level));
+
+        // emissive voxels keep their own light
+        let emitted = emission(map, neighbour, registry);
+        if channel == Channel::Block && emitted > 0 {
+          set_level(map, neighbour, channel, emitted);
+          relight.push_back(neighbour);
+        }
+      } else {
+        relight.push_back(neighbour);
+      }
+    }
+  }
+
+  propagate(map, registry, channel, relight);
+}
+
+/**
+ * Computes the light of a chunk that has just been loaded, and spreads it to the loaded chunks around it
+ * the chunks that are not lit yet must have no light, so that they don't spread light they don't have (see `update_light`)
+ */
+fn light_chunk(map: &mut ChunkMap, coord: IVec3, registry: &BlockRegistry) {
+  let size = CHUNK_SIZE;
+  let origin = ChunkMap::chunk_origin(coord);
+
+  // the columns that see the sky, the chunk is open to the sky if the chunk above isn't loaded
+  let open: Vec<bool> = match map.get(coord + IVec3::Y) {
+    Some(above) => (0..size * size)
+      .map(|i| above.light(i / size, 0, i % size).sky() == MAX_LIGHT)
+      .collect(),
+    None => vec![true; size * size],
+  };
+
+  let Some(chunk) = map.get_mut(coord) else {
+    return;
+  };
+
+  let mut sky_queue = VecDeque::new();
+  let mut block_queue = VecDeque::new();
+
+  // sunlight goes straight down the columns until it reaches an opaque voxel
+  for x in 0..size {
+    for z in 0..size {
+      if !open[x * size + z] {
+        continue;
+      }
+
+      for y in (0..size).rev() {
+        if registry.get(chunk.get(x, y, z)).opaque {
+          break;
+        }
+        chunk.set_light(x, y, z, Light::SKY);
+      }
+    }
+  }
+
+  // only the sunlit voxels next to darker ones can spread their light
+  let local = |x: usize, y: usize, z: usize| origin + IVec3::new(x as i32, y as i32, z as i32);
+  for ((x, y, z), _) in chunk.iter() {
+    if chunk.light(x, y, z).sky() != MAX_LIGHT {
+      continue;
+    }
+
+    let darker_neighbour = (0..6).any(|d| {
+      let n = IVec3::new(x as i32, y as i32, z as i32) + direction_offset(d);
+      if n.cmplt(IVec3::ZERO).any() || n.cmpge(IVec3::splat(size as i32)).any() {
+        return true;
+      }
+      chunk.light(n.x as usize, n.y as usize, n.z as usize).sky() != MAX_LIGHT
+    });
+    if darker_neighbour {
+      sky_queue.push_back(local(x, y, z));
+    }
+  }
+
+  if chunk
+    .palette()
+    .iter()
+    .any(|block| registry.get(*block).emissive > 0)
+  {
+    for x in 0..size {
+      for y in 0..size {
+        for z in 0..size {
+          let emitted = registry.get(chunk.get(x, y, z)).emissive.min(MAX_LIGHT);
+          if emitted > 0 {
+            chunk.set_light(x, y, z, chunk.light(x, y, z).with_block(emitted));
+            block_queue.push_back(local(x, y, z));
+          }
+        }
+      }
+    }
+  }
+
+  // the light of the neighbouring chunks flows in through the sides of the chunk
+  for d in 0..6 {
+    let offset = direction_offset(d);
+    for a in 0..size as i32 {
+      for b in 0..size as i32 {
+        // the voxel of the neighbour touching the side of the chunk
+        let mut voxel = [a, b, 0];
+        let axis = (0..3).find(|axis| offset[*axis] != 0).unwrap_or(0);
+        voxel.rotate_right(2 - axis);
+        voxel[axis] = if offset[axis] > 0 { size as i32 } else { -1 };
+        let voxel = origin + IVec3::from_array(voxel);
+
+        if let Some(light) = map.get_light(voxel) {
+          if light.sky() > 0 {
+            sky_queue.push_back(voxel);
+          }
+          if light.block() > 0 {
+            block_queue.push_back(voxel);
+          }
+        }
+      }
+    }
+  }
+
+  propagate(map, registry, Channel::Sky, sky_queue);
+  propagate(map, registry, Channel::Block, block_queue);
+
+  // the chunk below thought it was open to the sky, take the sunlight away from the columns this chunk covers
+  let top_of_below = origin - IVec3::Y;
+  let mut removed = VecDeque::new();
+  for x in 0..size as i32 {
+    for z in 0..size as i32 {
+      let above = origin + IVec3::new(x, 0, z);
+      let below = top_of_below + IVec3::new(x, 0, z);
+      if level(map, above, Channel::Sky) != Some(MAX_LIGHT)
+        && level(map, below, Channel::Sky) == Some(MAX_LIGHT)
+      {
+        set_level(map, below, Channel::Sky, 0);
+        removed.push_back((below, MAX_LIGHT));
+      }
+    }
+  }
+  unpropagate(map, registry, Channel::Sky, removed);
+
+  if let Some(chunk) = map.get_mut(coord) {
+    chunk.compact_light();
+  }
+}
+
+/**
+ * Updates the light around a voxel that has just been edited
+ */
+fn light_voxel(map: &mut ChunkMap, voxel: IVec3, registry: &BlockRegistry) {
+  let Some(block) = map.get_voxel(voxel) else {
+    return;
+  };
+  let properties = registry.get(block);
+
+  for channel in [Channel::Sky, Channel::Block] {
+    let Some(previous) = level(map, voxel, channel) else {
+      continue;
+    };
+
+    if previous > 0 {
+      set_level(map, voxel, channel, 0);
+      unpropagate(map, registry, channel, VecDeque::from([(voxel, previous)]));
+    }
+
+    let emitted = if channel == Channel::Block {
+      properties.emissive.min(MAX_LIGHT)
+    } else {
+      0
+    };
+    if emitted > level(map, voxel, channel).unwrap_or(0) {
+      set_level(map, voxel, channel, emitted);
+    }
+
+    // the light around the voxel flows back into it
+    let mut queue: VecDeque<IVec3> = (0..6).map(|d| voxel + direction_offset(d)).collect();
+    queue.push_back(voxel);
+    propagate(map, registry, channel, queue);
+  }
+}
+
+/**
+ * Lights the chunks that have been loaded and updates the light around the voxels that have been edited since the last call
+ */
+pub fn update_light(map: &mut ChunkMap, registry: &BlockRegistry) {
+  let mut chunks = map.take_unlit_chunks();
+  for coord in &chunks {
+    if let Some(chunk) = map.get_mut(*coord) {
+      chunk.fill_light(Light::default());
+    }
+  }
+
+  // chunks from the top down, so that sunlight comes from the chunks above when they are loaded together
+  chunks.sort_unstable_by_key(|coord| (-coord.y, coord.x, coord.z));
+  for coord in chunks {
+    light_chunk(map, coord, registry);
+  }
+
+  for voxel in map.take_light_updates() {
+    light_voxel(map, voxel, registry);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::block::blocks;
+  use crate::geometry::chunk::Chunk;
+
+  /**
+   * A world of 2x2x2 chunks: a stone floor (the chunks below y = 0) and open air above it
+   */
+  fn test_world() -> ChunkMap {
+    let mut map = ChunkMap::new();
+    for x in 0..2 {
+      for z in 0..2 {
+        map.insert(
+          IVec3::new(x, -1, z),
+          Chunk::filled(CHUNK_SIZE, blocks::STONE),
+        );
+        map.insert(IVec3::new(x, 0, z), Chunk::new(CHUNK_SIZE));
+      }
+    }
+    map
+  }
+
+  fn sky(map: &ChunkMap, x: i32, y: i32, z: i32) -> u8 {
+    map.get_light(IVec3::new(x, y, z)).unwrap().sky()
+  }
+
+  fn block(map: &ChunkMap, x: i32, y: i32, z: i32) -> u8 {
+    map.get_light(IVec3::new(x, y, z)).unwrap().block()
+  }
+
+  #[test]
+  fn sunlight_fills_open_air_and_spreads_under_roofs() {
+    let registry = BlockRegistry::default();
+    let mut map = test_world();
+
+    // a roof from x = 10 to 40 at y = 5, crossing the border between chunks
+    for x in 10..=40 {
+      for z in 10..=20 {
+        map.set_voxel(IVec3::new(x, 5, z), blocks::STONE);
+      }
+    }
+    map.take_light_updates();
+    update_light(&mut map, &registry);
+
+    assert_eq!(sky(&map, 0, 0, 0), MAX_LIGHT);
+    assert_eq!(sky(&map, 20, 6, 15), MAX_LIGHT);
+    assert_eq!(sky(&map, 0, -1, 0), 0);
+
+    // under the roof, the light fades with the distance from its edge
+    assert_eq!(sky(&map, 10, 0, 15), MAX_LIGHT - 1);
+    assert_eq!(sky(&map, 12, 0, 15), MAX_LIGHT - 3);
+    assert_eq!(sky(&map, 25, 0, 15), MAX_LIGHT - 6);
+    assert_eq!(sky(&map, 38, 4, 15), MAX_LIGHT - 3);
+  }
+
+  #[test]
+  fn edits_update_the_light_incrementally() {
+    let registry = BlockRegistry::default();
+    let mut map = test_world();
+    update_light(&mut map, &registry);
+
+    // covering a column takes the sunlight away from it
+    let roof = IVec3::new(31, 10, 31);
+    map.set_voxel(roof, blocks::STONE);
+    update_light(&mut map, &registry);
+    assert_eq!(sky(&map, 31, 9, 31), MAX_LIGHT - 1);
+    assert_eq!(sky(&map, 31, 0, 31), MAX_LIGHT - 1);
+
+    map.set_voxel(roof, blocks::AIR);
+    update_light(&mut map, &registry);
+    assert_eq!(sky(&map, 31, 0, 31), MAX_LIGHT);
+
+    // a lamp lights up its surroundings, across chunks, and takes its light away when broken
+    let lamp = IVec3::new(31, 1, 31);
+    map.set_voxel(lamp, blocks::LAMP);
+    update_light(&mut map, &registry);
+    assert_eq!(block(&map, 31, 1, 31), MAX_LIGHT);
+    assert_eq!(block(&map, 32, 1, 31), MAX_LIGHT - 1);
+    assert_eq!(block(&map, 35, 2, 30), MAX_LIGHT - 6);
+    assert_eq!(block(&map, 31, 0, 31), MAX_LIGHT - 1);
+
+    map.set_voxel(lamp, blocks::AIR);
+    update_light(&mut map, &registry);
+    for x in 20..40 {
+      assert_eq!(block(&map, x, 1, 31), 0);
+    }
+  }
+
+  #[test]
+  fn loading_a_chunk_above_shades_the_chunks_below() {
+    let registry = BlockRegistry::default();
+    let mut map = test_world();
+    update_light(&mut map, &registry);
+    assert_eq!(sky(&map, 5, 0, 5), MAX_LIGHT);
+
+    // a solid chunk loaded above (0, 0, 0) casts a shadow on it
+    map.insert(
+      IVec3::new(0, 1, 0),
+      Chunk::filled(CHUNK_SIZE, blocks::STONE),
+    );
+    update_light(&mut map, &registry);
+    assert_eq!(sky(&map, 5, 0, 5), 0);
+    assert_eq!(sky(&map, 31, 0, 5), MAX_LIGHT - 1);
+    assert_eq!(sky(&map, 31, 31, 31), MAX_LIGHT - 1);
+  }
+}
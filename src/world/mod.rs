@@ -0,0 +1,9 @@
+pub mod chunkmap;
+pub mod generation;
+pub mod lighting;
+pub mod noise;
+pub mod picking;
+pub mod player;
+pub mod region;
+pub mod streaming;
+pub mod vox;
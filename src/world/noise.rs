@@ -0,0 +1,185 @@
+/*!
+ * Seeded gradient (Perlin style) noise
+ * gradients are picked by hashing the lattice coordinates with the seed, so the same seed and position always give the same value
+ */
+
+/**
+ * Mixes a seed and a lattice point into a pseudo random 64 bit value (splitmix64 finalizer)
+ */
+#[inline]
+fn hash(seed: u64, x: i32, y: i32, z: i32) -> u64 {
+  let mut h = seed
+    ^ (x as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+    ^ (y as u64).wrapping_mul(0xC2B2_AE3D_27D4_EB4F)
+    ^ (z as u64).wrapping_mul(0x1656_67B1_9E37_79F9);
+  h = (h ^ (h >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+  h = (h ^ (h >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+  h ^ (h >> 31)
+}
+
+/**
+ * Quintic interpolation curve, has zero first and second derivatives at 0 and 1
+ */
+#[inline]
+fn fade(t: f32) -> f32 {
+  t * t * t * (t * (t * 6.0 - 15.0) + 10.0)
+}
+
+#[inline]
+fn lerp(a: f32, b: f32, t: f32) -> f32 {
+  a + (b - a) * t
+}
+
+/**
+ * Dot product between the gradient of a 2d lattice point and the offset from it
+ */
+#[inline]
+fn gradient_2d(seed: u64, x: i32, z: i32, dx: f32, dz: f32) -> f32 {
+  match hash(seed, x, 0, z) & 7 {
+    0 => dx + dz,
+    1 => dx - dz,
+    2 => -dx + dz,
+    3 => -dx - dz,
+    4 => dx,
+    5 => -dx,
+    6 => dz,
+    _ => -dz,
+  }
+}
+
+/**
+ * Dot product between the gradient of a 3d lattice point and the offset from it
+ * the gradients are the 12 edges of a cube (as in improved Perlin noise)
+ */
+#[inline]
+fn gradient_3d(seed: u64, x: i32, y: i32, z: i32, dx: f32, dy: f32, dz: f32) -> f32 {
+  match hash(seed, x, y, z) % 12 {
+    0 => dx + dy,
+    1 => -dx + dy,
+    2 => dx - dy,
+    3 => -dx - dy,
+    4 => dx + dz,
+    5 => -dx + dz,
+    6 => dx - dz,
+    7 => -dx - dz,
+    8 => dy + dz,
+    9 => -dy + dz,
+    10 => dy - dz,
+    _ => -dy - dz,
+  }
+}
+
+/**
+ * 2d gradient noise, roughly in the range [-1, 1]
+ */
+pub fn noise_2d(seed: u64, x: f32, z: f32) -> f32 {
+  let (x0, z0) = (x.floor(), z.floor());
+  let (dx, dz) = (x - x0, z - z0);
+  let (ix, iz) = (x0 as i32, z0 as i32);
+  let (u, v) = (fade(dx), fade(dz));
+
+  let a = lerp(
+    gradient_2d(seed, ix, iz, dx, dz),
+    gradient_2d(seed, ix + 1, iz, dx - 1.0, dz),
+    u,
+  );
+  let b = lerp(
+    gradient_2d(seed, ix, iz + 1, dx, dz - 1.0),
+    gradient_2d(seed, ix + 1, iz + 1, dx - 1.0, dz - 1.0),
+    u,
+  );
+
+  lerp(a, b, v)
+}
+
+/**
+ * 3d gradient noise, roughly in the range [-1, 1]
+ */
+pub fn noise_3d(seed: u64, x: f32, y: f32, z: f32) -> f32 {
+  let (x0, y0, z0) = (x.floor(), y.floor(), z.floor());
+  let (dx, dy, dz) = (x - x0, y - y0, z - z0);
+  let (ix, iy, iz) = (x0 as i32, y0 as i32, z0 as i32);
+  let (u, v, w) = (fade(dx), fade(dy), fade(dz));
+
+  let corner = |ox: i32, oy: i32, oz: i32| {
+    gradient_3d(
+      seed,
+      ix + ox,
+      iy + oy,
+      iz + oz,
+      dx - ox as f32,
+      dy - oy as f32,
+      dz - oz as f32,
+    )
+  };
+
+  let x00 = lerp(corner(0, 0, 0), corner(1, 0, 0), u);
+  let x10 = lerp(corner(0, 1, 0), corner(1, 1, 0), u);
+  let x01 = lerp(corner(0, 0, 1), corner(1, 0, 1), u);
+  let x11 = lerp(corner(0, 1, 1), corner(1, 1, 1), u);
+
+  lerp(lerp(x00, x10, v), lerp(x01, x11, v), w)
+}
+
+/**
+ * Sums `octaves` layers of 2d noise, each one with double the frequency and half the amplitude of the previous one
+ * the result is normalized to roughly [-1, 1], 0 octaves give 0
+ */
+pub fn fbm_2d(seed: u64, x: f32, z: f32, octaves: u32) -> f32 {
+  let mut sum = 0.0;
+  let mut amplitude = 1.0;
+  let mut frequency = 1.0;
+  let mut total = 0.0;
+
+  for octave in 0..octaves {
+    let octave_seed = seed.wrapping_add(octave as u64);
+    sum += noise_2d(octave_seed, x * frequency, z * frequency) * amplitude;
+    total += amplitude;
+    amplitude *= 0.5;
+    frequency *= 2.0;
+  }
+
+  // no octaves, no noise
+  if total > 0.0 {
+    sum / total
+  } else {
+    0.0
+  }
+}
+
+/**
+ * Sums `octaves` layers of 3d noise (see `fbm_2d`)
+ */
+pub fn fbm_3d(seed: u64, x: f32, y: f32, z: f32, octaves: u32) -> f32 {
+  let mut sum = 0.0;
+  let mut amplitude = 1.0;
+  let mut frequency = 1.0;
+  let mut total = 0.0;
+
+  for octave in 0..octaves {
+    let octave_seed = seed.wrapping_add(octave as u64);
+    sum += noise_3d(octave_seed, x * frequency, y * frequency, z * frequency) * amplitude;
+    total += amplitude;
+    amplitude *= 0.5;
+    frequency *= 2.0;
+  }
+
+  // no octaves, no noise
+  if total > 0.0 {
+    sum / total
+  } else {
+    0.0
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn fbm_without_octaves_is_zero() {
+    assert_eq!(fbm_2d(7, 0.3, 0.6, 0), 0.0);
+    assert_eq!(fbm_3d(7, 0.3, 0.6, 0.9, 0), 0.0);
+    assert!(fbm_2d(7, 0.3, 0.6, 4).is_finite());
+  }
+}
@@ -0,0 +1,200 @@
+use bevy::pbr::NotShadowCaster;
+use bevy::prelude::*;
+use bevy::render::mesh::{Indices, PrimitiveTopology};
+use bevy::window::{CursorGrabMode, PrimaryWindow};
+
+use super::chunkmap::ChunkMap;
+use super::player::{PlayerController, PlayerSettings};
+use crate::block::{blocks, BlockId, BlockRegistry};
+use crate::geometry::raycast::RaycastHit;
+
+/**
+ * Marks the camera rays are cast from to pick voxels with the cursor
+ */
+#[derive(Component, Default)]
+pub struct VoxelPicker;
+
+/**
+ * The voxel under the cursor, updated every frame
+ */
+#[derive(Resource, Default, Debug)]
+pub struct TargetedVoxel(pub Option<RaycastHit>);
+
+/**
+ * Settings of voxel picking
+ * `reach`: how far from the camera voxels can be picked
+ * `place_block`: the block placed against the targeted voxel
+ * `break_button`: the mouse button that removes the targeted voxel
+ * `place_button`: the mouse button that places `place_block` against the targeted face
+ */
+#[derive(Resource, Clone, Debug)]
+pub struct PickingSettings {
+  pub reach: f32,
+  pub place_block: BlockId,
+  pub break_button: MouseButton,
+  pub place_button: MouseButton,
+}
+
+impl Default for PickingSettings {
+  fn default() -> Self {
+    Self {
+      reach: 64.0,
+      place_block: blocks::STONE,
+      break_button: MouseButton::Left,
+      // the right button is used by the orbit camera to pan
+      place_button: MouseButton::Middle,
+    }
+  }
+}
+
+/**
+ * Marks the outline drawn around the targeted voxel
+ */
+#[derive(Component)]
+struct TargetOutline;
+
+/**
+ * Casts a ray from the `VoxelPicker` camera through the cursor (or the middle of the screen while the cursor is grabbed), outlines the voxel it hits and lets the mouse break and place blocks
+ * needs the resources of the `ChunkStreamingPlugin`
+ */
+pub struct VoxelPickingPlugin;
+
+impl Plugin for VoxelPickingPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .init_resource::<TargetedVoxel>()
+      .init_resource::<PickingSettings>()
+      .add_startup_system(setup_target_outline)
+      .add_systems((update_target, edit_target, update_target_outline).chain());
+  }
+}
+
+/**
+ * Returns the edges of a unit cube, slightly bigger than a voxel so that the outline isn't hidden by its faces
+ */
+fn outline_mesh() -> Mesh {
+  let (min, max) = (-0.002, 1.002);
+  let corners: Vec<[f32; 3]> = (0..8)
+    .map(|i| {
+      let pick = |bit: usize| if i & bit != 0 { max } else { min };
+      [pick(1), pick(2), pick(4)]
+    })
+    .collect();
+
+  // every pair of corners that differ on a single axis
+  let mut edges = Vec::new();
+  for i in 0..8u32 {
+    for bit in [1, 2, 4] {
+      if i & bit == 0 {
+        edges.extend([i, i | bit]);
+      }
+    }
+  }
+
+  let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+  mesh.insert_attribute(Mesh::ATTRIBUTE_POSITION, corners);
+  mesh.set_indices(Some(Indices::U32(edges)));
+  mesh
+}
+
+fn setup_target_outline(
+  mut commands: Commands,
+  mut meshes: ResMut<Assets<Mesh>>,
+  mut materials: ResMut<Assets<StandardMaterial>>,
+) {
+  commands.spawn((
+    PbrBundle {
+      mesh: meshes.add(outline_mesh()),
+      material: materials.add(StandardMaterial {
+        base_color: Color::BLACK,
+        unlit: true,
+        ..default()
+      }),
+      visibility: Visibility::Hidden,
+      ..default()
+    },
+    NotShadowCaster,
+    TargetOutline,
+  ));
+}
+
+fn update_target(
+  mut target: ResMut<TargetedVoxel>,
+  windows: Query<&Window, With<PrimaryWindow>>,
+  cameras: Query<(&Camera, &GlobalTransform), With<VoxelPicker>>,
+  chunk_map: Res<ChunkMap>,
+  registry: Res<BlockRegistry>,
+  settings: Res<PickingSettings>,
+) {
+  let cursor = windows
+    .get_single()
+    .ok()
+    .and_then(|window| match window.cursor.grab_mode {
+      CursorGrabMode::None => window.cursor_position(),
+      // a grabbed cursor is hidden, the ray goes through the middle of the screen
+      _ => Some(Vec2::new(window.width(), window.height()) / 2.0),
+    });
+
+  let ray = cursor
+    .zip(cameras.get_single().ok())
+    .and_then(|(cursor, (camera, transform))| camera.viewport_to_world(transform, cursor));
+
+  target.0 =
+    ray.and_then(|ray| chunk_map.raycast(ray.origin, ray.direction, settings.reach, &registry));
+}
+
+/**
+ * Breaks or places a block at the targeted voxel
+ * blocks are never placed inside an enabled `PlayerController`, which would get stuck in them
+ */
+fn edit_target(
+  target: Res<TargetedVoxel>,
+  mut chunk_map: ResMut<ChunkMap>,
+  registry: Res<BlockRegistry>,
+  settings: Res<PickingSettings>,
+  mouse_buttons: Res<Input<MouseButton>>,
+  players: Query<(&PlayerController, &GlobalTransform)>,
+  player_settings: Option<Res<PlayerSettings>>,
+) {
+  let Some(hit) = target.0 else {
+    return;
+  };
+
+  if mouse_buttons.just_pressed(settings.break_button) {
+    chunk_map.set_voxel(hit.voxel, blocks::AIR);
+  } else if mouse_buttons.just_pressed(settings.place_button) && hit.normal != IVec3::ZERO {
+    // blocks can only replace voxels that are not solid (air, water...)
+    let voxel = hit.voxel + hit.normal;
+    let blocked = player_settings.is_some_and(|player_settings| {
+      players.iter().any(|(player, transform)| {
+        player.enabled
+          && player_settings
+            .collider(transform.translation())
+            .overlaps_voxel(voxel)
+      })
+    });
+
+    if !blocked
+      && chunk_map
+        .get_voxel(voxel)
+        .is_some_and(|block| !registry.get(block).solid)
+    {
+      chunk_map.set_voxel(voxel, settings.place_block);
+    }
+  }
+}
+
+fn update_target_outline(
+  target: Res<TargetedVoxel>,
+  mut outlines: Query<(&mut Transform, &mut Visibility), With<TargetOutline>>,
+) {
+  for (mut transform, mut visibility) in &mut outlines {
+    match target.0 {
+      Some(hit) => {
+        transform.translation = hit.voxel.as_vec3();
+        *visibility = Visibility::Visible;
+      }
+      None => *visibility = Visibility::Hidden,
+    }
+  }
+}
@@ -0,0 +1,200 @@
+use bevy::input::mouse::MouseMotion;
+use bevy::prelude::*;
+use bevy::window::{CursorGrabMode, PrimaryWindow};
+
+use super::chunkmap::ChunkMap;
+use crate::block::BlockRegistry;
+use crate::geometry::collision::Aabb;
+
+/**
+ * A first person controller, moves the transform of its entity (the eyes of the player) with the keyboard and the mouse
+ * WASD to walk, space to jump, F to start or stop flying, space and left shift to fly up and down
+ * `enabled`: wether the controller reacts to input, the cursor is grabbed while a controller is enabled
+ * `flying`: wether the player ignores gravity
+ * `velocity`: the current velocity of the player (in voxels per second)
+ * `on_ground`: wether the player is standing on a solid voxel
+ * `yaw`, `pitch`: where the player is looking (in radians)
+ */
+#[derive(Component, Clone, Debug, Default)]
+pub struct PlayerController {
+  pub enabled: bool,
+  pub flying: bool,
+  pub velocity: Vec3,
+  pub on_ground: bool,
+  pub yaw: f32,
+  pub pitch: f32,
+}
+
+impl PlayerController {
+  /**
+   * Turns the player to look along `direction`
+   */
+  pub fn look_along(&mut self, direction: Vec3) {
+    let Some(direction) = direction.try_normalize() else {
+      return;
+    };
+
+    self.yaw = f32::atan2(-direction.x, -direction.z);
+    self.pitch = direction.y.asin();
+  }
+
+  pub fn rotation(&self) -> Quat {
+    Quat::from_euler(EulerRot::YXZ, self.yaw, self.pitch, 0.0)
+  }
+}
+
+/**
+ * Settings of the first person controller
+ * `walk_speed`, `fly_speed`: how fast the player moves (in voxels per second)
+ * `jump_speed`: the vertical speed of the player when it jumps
+ * `gravity`: how fast the player falls (in voxels per second squared)
+ * `mouse_sensitivity`: how much the player turns for every pixel the mouse moves (in radians)
+ * `half_width`, `height`: the size of the collision box of the player (see `Aabb::from_feet`)
+ * `eye_height`: how far above the feet the eyes of the player are
+ */
+#[derive(Resource, Clone, Debug)]
+pub struct PlayerSettings {
+  pub walk_speed: f32,
+  pub fly_speed: f32,
+  pub jump_speed: f32,
+  pub gravity: f32,
+  pub mouse_sensitivity: f32,
+  pub half_width: f32,
+  pub height: f32,
+  pub eye_height: f32,
+}
+
+impl PlayerSettings {
+  /**
+   * Returns the collision box of a player
+   * `eyes`: where the eyes of the player are, the translation of its entity
+   */
+  pub fn collider(&self, eyes: Vec3) -> Aabb {
+    Aabb::from_feet(
+      eyes - Vec3::Y * self.eye_height,
+      self.half_width,
+      self.height,
+    )
+  }
+}
+
+impl Default for PlayerSettings {
+  fn default() -> Self {
+    Self {
+      walk_speed: 5.0,
+      fly_speed: 15.0,
+      jump_speed: 8.0,
+      gravity: 25.0,
+      mouse_sensitivity: 0.002,
+      half_width: 0.3,
+      height: 1.8,
+      eye_height: 1.6,
+    }
+  }
+}
+
+/**
+ * Walks, jumps and flies the entities with a `PlayerController` through the voxels of the world
+ * needs the resources of the `ChunkStreamingPlugin`
+ */
+pub struct PlayerControllerPlugin;
+
+impl Plugin for PlayerControllerPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .init_resource::<PlayerSettings>()
+      .add_systems((grab_cursor, look_player, move_player).chain());
+  }
+}
+
+fn grab_cursor(
+  mut windows: Query<&mut Window, With<PrimaryWindow>>,
+  players: Query<&PlayerController>,
+) {
+  let grabbed = players.iter().any(|player| player.enabled);
+  let grab_mode = if grabbed {
+    CursorGrabMode::Locked
+  } else {
+    CursorGrabMode::None
+  };
+
+  for mut window in &mut windows {
+    // only touch the window when something changes, to not trigger change detection every frame
+    if window.cursor.grab_mode != grab_mode {
+      window.cursor.grab_mode = grab_mode;
+      window.cursor.visible = !grabbed;
+    }
+  }
+}
+
+fn look_player(
+  mut mouse_motion: EventReader<MouseMotion>,
+  mut players: Query<(&mut PlayerController, &mut Transform)>,
+  settings: Res<PlayerSettings>,
+) {
+  let delta: Vec2 = mouse_motion.iter().map(|motion| motion.delta).sum();
+
+  for (mut player, mut transform) in &mut players {
+    if !player.enabled {
+      continue;
+    }
+
+    player.yaw -= delta.x * settings.mouse_sensitivity;
+    player.pitch = (player.pitch - delta.y * settings.mouse_sensitivity).clamp(-1.55, 1.55);
+    transform.rotation = player.rotation();
+  }
+}
+
+fn move_player(
+  mut players: Query<(&mut PlayerController, &mut Transform)>,
+  keys: Res<Input<KeyCode>>,
+  time: Res<Time>,
+  chunk_map: Res<ChunkMap>,
+  registry: Res<BlockRegistry>,
+  settings: Res<PlayerSettings>,
+) {
+  // long frames (e.g. while chunks load) would make the player jump through the world
+  let dt = time.delta_seconds().min(0.1);
+
+  for (mut player, mut transform) in &mut players {
+    if !player.enabled {
+      continue;
+    }
+
+    if keys.just_pressed(KeyCode::F) {
+      player.flying = !player.flying;
+      player.velocity = Vec3::ZERO;
+    }
+
+    // the input is relative to where the player is looking, horizontally
+    let forward = Quat::from_rotation_y(player.yaw) * Vec3::NEG_Z;
+    let right = Quat::from_rotation_y(player.yaw) * Vec3::X;
+    let axis = |positive: KeyCode, negative: KeyCode| {
+      keys.pressed(positive) as i32 as f32 - keys.pressed(negative) as i32 as f32
+    };
+    let wish = (forward * axis(KeyCode::W, KeyCode::S) + right * axis(KeyCode::D, KeyCode::A))
+      .normalize_or_zero();
+
+    if player.flying {
+      let vertical = axis(KeyCode::Space, KeyCode::LShift);
+      player.velocity = (wish + Vec3::Y * vertical) * settings.fly_speed;
+    } else {
+      let fall = player.velocity.y - settings.gravity * dt;
+      player.velocity = wish * settings.walk_speed + Vec3::Y * fall;
+
+      if player.on_ground && keys.pressed(KeyCode::Space) {
+        player.velocity.y = settings.jump_speed;
+      }
+    }
+
+    let aabb = settings.collider(transform.translation);
+    let sweep = chunk_map.sweep(&aabb, player.velocity * dt, &registry);
+
+    player.on_ground = sweep.collided.y && player.velocity.y < 0.0;
+    if sweep.collided.y {
+      player.velocity.y = 0.0;
+    }
+
+    transform.translation += sweep.motion;
+  }
+}
@@ -0,0 +1,467 @@
+/*!
+ * Region files, the on-disk format of the world
+ *
+ * the world is split into regions of `REGION_SIZE^3` chunks, each region is stored in its own file:
+ * - a header: the magic bytes `VOXR`, the format version, the size of the chunks and the size of the region (3 little endian `u32`)
+ * - an offset table with one entry per chunk of the region, in the layout of `Chunk::index`:
+ *   the position of the chunk in the file and its length (2 little endian `u32`, a length of 0 means the chunk is not stored)
+ * - the zlib compressed data of the chunks
+ *
+ * only chunks that have been edited are ever stored, the others are generated again when they are loaded
+ */
+
+use std::fs::{self, File};
+use std::io::{self, Cursor, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+use flate2::read::ZlibDecoder;
+use flate2::write::ZlibEncoder;
+use flate2::Compression;
+
+use super::chunkmap::CHUNK_SIZE;
+use crate::geometry::chunk::Chunk;
+
+/**
+ * The amount of chunks in each side of a region
+ */
+pub const REGION_SIZE: i32 = 8;
+
+/**
+ * The version of the format written by this code
+ * when the format changes, bump it and convert the older versions when they are read (see `RegionFile::read`)
+ */
+pub const REGION_VERSION: u32 = 1;
+
+const MAGIC: &[u8; 4] = b"VOXR";
+const REGION_VOLUME: usize = (REGION_SIZE * REGION_SIZE * REGION_SIZE) as usize;
+const HEADER_LENGTH: u64 = 16;
+const TABLE_LENGTH: u64 = REGION_VOLUME as u64 * 8;
+
+fn invalid_data(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+  let mut bytes = [0; 4];
+  reader.read_exact(&mut bytes)?;
+  Ok(u32::from_le_bytes(bytes))
+}
+
+/**
+ * Reads the compressed data of a chunk, at the position and with the length given by the offset table
+ * the data grows as it is read, so a corrupt length fails once the file ends instead of allocating it all upfront
+ */
+fn read_chunk_data(
+  reader: &mut (impl Read + Seek),
+  offset: u32,
+  length: u32,
+) -> io::Result<Vec<u8>> {
+  reader.seek(SeekFrom::Start(offset as u64))?;
+
+  let mut data = Vec::new();
+  reader.take(length as u64).read_to_end(&mut data)?;
+  if data.len() != length as usize {
+    return Err(invalid_data(format!(
+      "the chunk at {offset} is cut short, {} of its {length} bytes are in the file",
+      data.len()
+    )));
+  }
+
+  Ok(data)
+}
+
+/**
+ * Returns the coordinates of the region containing a chunk
+ */
+pub fn region_coord(chunk: IVec3) -> IVec3 {
+  IVec3::new(
+    chunk.x.div_euclid(REGION_SIZE),
+    chunk.y.div_euclid(REGION_SIZE),
+    chunk.z.div_euclid(REGION_SIZE),
+  )
+}
+
+/**
+ * Returns the index of a chunk in the offset table of its region
+ */
+fn region_slot(chunk: IVec3) -> usize {
+  let (x, y, z) = (
+    chunk.x.rem_euclid(REGION_SIZE),
+    chunk.y.rem_euclid(REGION_SIZE),
+    chunk.z.rem_euclid(REGION_SIZE),
+  );
+  ((x * REGION_SIZE + y) * REGION_SIZE + z) as usize
+}
+
+/**
+ * Reads the header of a region file and checks that it can be read
+ * returns the version of the file
+ */
+fn read_header(reader: &mut impl Read, chunk_size: usize) -> io::Result<u32> {
+  let mut magic = [0; 4];
+  reader.read_exact(&mut magic)?;
+  if &magic != MAGIC {
+    return Err(invalid_data("not a region file".to_string()));
+  }
+
+  let version = read_u32(reader)?;
+  if version == 0 || version > REGION_VERSION {
+    return Err(invalid_data(format!(
+      "unsupported region version {version}"
+    )));
+  }
+
+  let (file_chunk_size, region_size) = (read_u32(reader)?, read_u32(reader)?);
+  if file_chunk_size as usize != chunk_size || region_size as i32 != REGION_SIZE {
+    return Err(invalid_data(format!(
+      "region of {region_size}^3 chunks of size {file_chunk_size}, expected {REGION_SIZE}^3 chunks of size {chunk_size}"
+    )));
+  }
+
+  Ok(version)
+}
+
+fn compress_chunk(chunk: &Chunk) -> io::Result<Vec<u8>> {
+  let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+  encoder.write_all(&chunk.to_voxels())?;
+  encoder.finish()
+}
+
+/**
+ * Decompresses the data of a chunk
+ * `version`: the version of the file the data comes from
+ */
+fn decompress_chunk(data: &[u8], chunk_size: usize, version: u32) -> io::Result<Chunk> {
+  let mut voxels = Vec::new();
+  ZlibDecoder::new(data).read_to_end(&mut voxels)?;
+
+  match version {
+    // the raw voxels of the chunk
+    1 => Chunk::from_voxels(chunk_size, voxels)
+      .ok_or_else(|| invalid_data("chunk with the wrong size".to_string())),
+    _ => Err(invalid_data(format!(
+      "unsupported region version {version}"
+    ))),
+  }
+}
+
+/**
+ * A region loaded in memory, the chunks are kept compressed
+ * `chunk_size`: the size of the chunks of the region
+ * `chunks`: the compressed data of each chunk of the region, empty if the chunk is not stored
+ */
+pub struct RegionFile {
+  pub chunk_size: usize,
+  chunks: Vec<Vec<u8>>,
+}
+
+impl RegionFile {
+  pub fn new(chunk_size: usize) -> Self {
+    Self {
+      chunk_size,
+      chunks: vec![Vec::new(); REGION_VOLUME],
+    }
+  }
+
+  /**
+   * Reads a whole region
+   * files written by older versions are converted to the current one
+   */
+  pub fn read(reader: &mut (impl Read + Seek), chunk_size: usize) -> io::Result<Self> {
+    let version = read_header(reader, chunk_size)?;
+    let table = (0..REGION_VOLUME)
+      .map(|_| Ok((read_u32(reader)?, read_u32(reader)?)))
+      .collect::<io::Result<Vec<_>>>()?;
+
+    let mut region = Self::new(chunk_size);
+    for (slot, (offset, length)) in table.into_iter().enumerate() {
+      if length == 0 {
+        continue;
+      }
+
+      let data = read_chunk_data(reader, offset, length)?;
+
+      region.chunks[slot] = if version == REGION_VERSION {
+        data
+      } else {
+        compress_chunk(&decompress_chunk(&data, chunk_size, version)?)?
+      };
+    }
+
+    Ok(region)
+  }
+
+  /**
+   * Reads a single chunk from a region, without reading the rest of the region
+   * `chunk`: the coordinates of the chunk in the world
+   */
+  pub fn read_chunk(
+    reader: &mut (impl Read + Seek),
+    chunk_size: usize,
+    chunk: IVec3,
+  ) -> io::Result<Option<Chunk>> {
+    let version = read_header(reader, chunk_size)?;
+
+    reader.seek(SeekFrom::Start(
+      HEADER_LENGTH + region_slot(chunk) as u64 * 8,
+    ))?;
+    let (offset, length) = (read_u32(reader)?, read_u32(reader)?);
+    if length == 0 {
+      return Ok(None);
+    }
+
+    let data = read_chunk_data(reader, offset, length)?;
+
+    decompress_chunk(&data, chunk_size, version).map(Some)
+  }
+
+  /**
+   * Writes the whole region, in the current version of the format
+   */
+  pub fn write(&self, writer: &mut impl Write) -> io::Result<()> {
+    writer.write_all(MAGIC)?;
+    for value in [REGION_VERSION, self.chunk_size as u32, REGION_SIZE as u32] {
+      writer.write_all(&value.to_le_bytes())?;
+    }
+
+    let mut offset = HEADER_LENGTH + TABLE_LENGTH;
+    for data in &self.chunks {
+      let position = if data.is_empty() { 0 } else { offset as u32 };
+      writer.write_all(&position.to_le_bytes())?;
+      writer.write_all(&(data.len() as u32).to_le_bytes())?;
+      offset += data.len() as u64;
+    }
+
+    for data in &self.chunks {
+      writer.write_all(data)?;
+    }
+
+    Ok(())
+  }
+
+  /**
+   * Returns a chunk of the region, or `None` if it is not stored
+   * `chunk`: the coordinates of the chunk in the world
+   */
+  pub fn get(&self, chunk: IVec3) -> io::Result<Option<Chunk>> {
+    let data = &self.chunks[region_slot(chunk)];
+    if data.is_empty() {
+      return Ok(None);
+    }
+
+    decompress_chunk(data, self.chunk_size, REGION_VERSION).map(Some)
+  }
+
+  /**
+   * Stores a chunk in the region, replacing the previous one
+   * `coord`: the coordinates of the chunk in the world
+   */
+  pub fn set(&mut self, coord: IVec3, chunk: &Chunk) -> io::Result<()> {
+    self.chunks[region_slot(coord)] = compress_chunk(chunk)?;
+    Ok(())
+  }
+}
+
+/**
+ * Where the chunks of the world are saved, when this resource exists chunks are loaded from it before being generated
+ * and edited chunks are saved to it when they are unloaded or when the app exits (see `ChunkStreamingPlugin`)
+ * `directory`: the directory with the region files
+ */
+#[derive(Resource, Clone, Debug)]
+pub struct WorldStorage {
+  pub directory: PathBuf,
+}
+
+impl WorldStorage {
+  pub fn new(directory: impl Into<PathBuf>) -> Self {
+    Self {
+      directory: directory.into(),
+    }
+  }
+
+  /**
+   * Returns the path of the file of a region
+   */
+  pub fn region_path(&self, region: IVec3) -> PathBuf {
+    self
+      .directory
+      .join(format!("r.{}.{}.{}.voxr", region.x, region.y, region.z))
+  }
+
+  /**
+   * Loads a chunk, returns `None` if it was never saved
+   */
+  pub fn load_chunk(&self, coord: IVec3) -> io::Result<Option<Chunk>> {
+    let mut file = match File::open(self.region_path(region_coord(coord))) {
+      Ok(file) => file,
+      Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+      Err(error) => return Err(error),
+    };
+
+    RegionFile::read_chunk(&mut file, CHUNK_SIZE, coord)
+  }
+
+  /**
+   * Saves chunks, every region they are in is rewritten once
+   * the regions are written to a temporary file first, so a crash never leaves a region half written
+   */
+  pub fn save_chunks<'a>(
+    &self,
+    chunks: impl IntoIterator<Item = (IVec3, &'a Chunk)>,
+  ) -> io::Result<()> {
+    let mut regions: HashMap<IVec3, Vec<(IVec3, &Chunk)>> = HashMap::default();
+    for (coord, chunk) in chunks {
+      regions
+        .entry(region_coord(coord))
+        .or_default()
+        .push((coord, chunk));
+    }
+
+    if regions.is_empty() {
+      return Ok(());
+    }
+    fs::create_dir_all(&self.directory)?;
+
+    for (region, chunks) in regions {
+      let path = self.region_path(region);
+
+      let mut file = match fs::read(&path) {
+        Ok(bytes) => RegionFile::read(&mut Cursor::new(bytes), CHUNK_SIZE)?,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => RegionFile::new(CHUNK_SIZE),
+        Err(error) => return Err(error),
+      };
+
+      for (coord, chunk) in chunks {
+        file.set(coord, chunk)?;
+      }
+
+      write_atomically(&path, |writer| file.write(writer))?;
+    }
+
+    Ok(())
+  }
+}
+
+fn write_atomically(
+  path: &Path,
+  write: impl FnOnce(&mut io::BufWriter<File>) -> io::Result<()>,
+) -> io::Result<()> {
+  let temporary = path.with_extension("tmp");
+
+  let mut writer = io::BufWriter::new(File::create(&temporary)?);
+  write(&mut writer)?;
+  writer
+    .into_inner()
+    .map_err(|error| error.into_error())?
+    .sync_all()?;
+
+  fs::rename(temporary, path)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::block::blocks;
+
+  fn test_chunk(size: usize, seed: usize) -> Chunk {
+    let mut chunk = Chunk::new(size);
+    for x in 0..size {
+      for y in 0..size {
+        for z in 0..size {
+          if (x * 7 + y * 3 + z + seed) % 5 == 0 {
+            chunk.set(x, y, z, blocks::STONE);
+          }
+        }
+      }
+    }
+    chunk
+  }
+
+  #[test]
+  fn region_round_trip() {
+    let mut region = RegionFile::new(4);
+    let coords = [IVec3::ZERO, IVec3::new(-1, 3, -9), IVec3::new(7, 7, 7)];
+    for (i, coord) in coords.iter().enumerate() {
+      region.set(*coord, &test_chunk(4, i)).unwrap();
+    }
+
+    let mut bytes = Vec::new();
+    region.write(&mut bytes).unwrap();
+
+    let read = RegionFile::read(&mut Cursor::new(&bytes), 4).unwrap();
+    for (i, coord) in coords.iter().enumerate() {
+      let expected = test_chunk(4, i);
+      assert_eq!(
+        read.get(*coord).unwrap().unwrap().to_voxels(),
+        expected.to_voxels()
+      );
+
+      let single = RegionFile::read_chunk(&mut Cursor::new(&bytes), 4, *coord)
+        .unwrap()
+        .unwrap();
+      assert_eq!(single.to_voxels(), expected.to_voxels());
+    }
+    assert!(read.get(IVec3::new(1, 0, 0)).unwrap().is_none());
+  }
+
+  #[test]
+  fn rejects_other_formats() {
+    let mut bytes = Vec::new();
+    RegionFile::new(4).write(&mut bytes).unwrap();
+
+    // wrong chunk size
+    assert!(RegionFile::read(&mut Cursor::new(&bytes), 8).is_err());
+
+    // a version from the future
+    let mut future = bytes.clone();
+    future[4..8].copy_from_slice(&(REGION_VERSION + 1).to_le_bytes());
+    assert!(RegionFile::read(&mut Cursor::new(&future), 4).is_err());
+
+    bytes[0] = b'X';
+    assert!(RegionFile::read(&mut Cursor::new(&bytes), 4).is_err());
+  }
+
+  #[test]
+  fn rejects_lengths_past_the_end_of_the_file() {
+    let mut region = RegionFile::new(4);
+    region.set(IVec3::ZERO, &test_chunk(4, 0)).unwrap();
+    let mut bytes = Vec::new();
+    region.write(&mut bytes).unwrap();
+
+    // the length of the first chunk of the table claims almost 4 GiB
+    let length = HEADER_LENGTH as usize + 4;
+    bytes[length..length + 4].copy_from_slice(&u32::MAX.to_le_bytes());
+
+    let error = RegionFile::read(&mut Cursor::new(&bytes), 4).err().unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    let error = RegionFile::read_chunk(&mut Cursor::new(&bytes), 4, IVec3::ZERO)
+      .err()
+      .unwrap();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+  }
+
+  #[test]
+  fn storage_saves_only_given_chunks() {
+    let directory = std::env::temp_dir().join(format!("voxel-region-test-{}", std::process::id()));
+    let storage = WorldStorage::new(&directory);
+
+    let chunk = test_chunk(CHUNK_SIZE, 1);
+    let (saved, other) = (IVec3::new(-3, 0, 12), IVec3::new(-3, 0, 13));
+    storage.save_chunks([(saved, &chunk)]).unwrap();
+    // a second save of the same region keeps the chunks already in it
+    storage
+      .save_chunks([(IVec3::new(-4, 1, 12), &chunk)])
+      .unwrap();
+
+    assert_eq!(
+      storage.load_chunk(saved).unwrap().unwrap().to_voxels(),
+      chunk.to_voxels()
+    );
+    assert!(storage.load_chunk(other).unwrap().is_none());
+    assert!(storage.load_chunk(IVec3::new(100, 0, 0)).unwrap().is_none());
+
+    fs::remove_dir_all(directory).unwrap();
+  }
+}
@@ -0,0 +1,591 @@
+use std::io;
+use std::sync::Arc;
+
+use bevy::app::AppExit;
+use bevy::diagnostic::{Diagnostic, DiagnosticId, Diagnostics};
+use bevy::prelude::*;
+use bevy::tasks::{AsyncComputeTaskPool, IoTaskPool, Task};
+use bevy::utils::{HashMap, HashSet};
+use futures_lite::future;
+
+use super::chunkmap::{ChunkMap, CHUNK_SIZE};
+use super::generation::WorldGenerator;
+use super::lighting::update_light;
+use super::region::WorldStorage;
+use crate::block::BlockRegistry;
+use crate::geometry::chunk::Chunk;
+use crate::geometry::lod::{lod_scale, MAX_LOD};
+use crate::geometry::mesher::ChunkMesher;
+use crate::geometry::quad::{direction_offset, ChunkMeshes};
+use crate::render::material::{ChunkMaterial, ChunkMaterials};
+
+/**
+ * The amount of loaded chunks
+ */
+pub const LOADED_CHUNKS: DiagnosticId = DiagnosticId::from_u128(0x9f1aa842925847059aebd5c129c8e89e);
+
+/**
+ * The memory used by the voxels of the loaded chunks, in KiB (see `Chunk::memory_usage`)
+ */
+pub const CHUNK_MEMORY: DiagnosticId = DiagnosticId::from_u128(0x9fce13b19aee4fd6b874d5520f7a538c);
+
+/**
+ * Marks an entity the world is loaded around (usually the camera)
+ */
+#[derive(Component, Default)]
+pub struct ChunkViewer;
+
+/**
+ * A chunk that has been spawned in the scene, the entity holds the opaque mesh of the chunk
+ * `coord`: the coordinates of the chunk in the `ChunkMap`
+ * `transparent`: the child entity holding the transparent mesh of the chunk (see `RenderLayer`)
+ * `lod`: the level of detail of the meshes of the chunk (see `PaddedChunk::downsampled`)
+ */
+#[derive(Component)]
+pub struct ChunkEntity {
+  pub coord: IVec3,
+  pub transparent: Entity,
+  pub lod: u32,
+}
+
+/**
+ * The meshes being built in the background for a chunk entity
+ * when the task is done the meshes replace the ones of the chunk, dropping the component cancels the task
+ * `task`: the task building the meshes
+ * `lod`: the level of detail of the meshes, the entity is scaled to match it
+ */
+#[derive(Component)]
+pub struct ChunkMeshTask {
+  task: Task<ChunkMeshes>,
+  lod: u32,
+}
+
+/**
+ * Controls how chunks are streamed in and out around the viewers
+ * `radius`: how far (in chunks) from a viewer chunks are loaded, horizontally
+ * `vertical_radius`: how far (in chunks) from a viewer chunks are loaded, vertically
+ * `load_budget`: the maximum amount of chunks generated per frame
+ * `mesh_budget`: the maximum amount of chunks sent to be meshed per frame
+ * `unload_budget`: the maximum amount of chunks unloaded per frame
+ * `lod_distances`: the distances (in chunks) from the viewers at which each lower level of detail starts,
+ *   closer chunks are meshed with every voxel, farther ones with fewer bigger voxels (see `PaddedChunk::downsampled`)
+ */
+#[derive(Resource, Clone, Debug)]
+pub struct StreamingSettings {
+  pub radius: i32,
+  pub vertical_radius: i32,
+  pub load_budget: usize,
+  pub mesh_budget: usize,
+  pub unload_budget: usize,
+  pub lod_distances: Vec<i32>,
+}
+
+impl Default for StreamingSettings {
+  fn default() -> Self {
+    Self {
+      radius: 6,
+      vertical_radius: 2,
+      load_budget: 4,
+      mesh_budget: 16,
+      unload_budget: 8,
+      lod_distances: vec![3, 5, 8],
+    }
+  }
+}
+
+/**
+ * The entities of the chunks that are currently loaded
+ */
+#[derive(Resource, Default)]
+pub struct ChunkEntities {
+  entities: HashMap<IVec3, Entity>,
+}
+
+impl ChunkEntities {
+  pub fn get(&self, coord: IVec3) -> Option<Entity> {
+    self.entities.get(&coord).copied()
+  }
+}
+
+/**
+ * A copy of the `BlockRegistry` shared with the mesh tasks, made again only when the registry changes
+ */
+#[derive(Resource, Default)]
+struct SharedRegistry(Arc<BlockRegistry>);
+
+/**
+ * The edited chunks being saved on the `IoTaskPool`, one save at a time so that a region is never written by two tasks at once
+ * the chunks stay loaded until the save is done, and are marked as modified again if it fails
+ * `task`: the task writing the chunks to the `WorldStorage`
+ * `coords`: the chunks being saved
+ */
+#[derive(Resource, Default)]
+struct PendingSave {
+  task: Option<Task<io::Result<()>>>,
+  coords: Vec<IVec3>,
+}
+
+/**
+ * The coordinates of the chunks the viewers are in, updated every frame
+ */
+#[derive(Resource, Default)]
+pub struct ViewerChunks {
+  pub centers: Vec<IVec3>,
+}
+
+/**
+ * Loads, meshes and unloads the chunks around every `ChunkViewer`
+ * the chunks are rendered with the materials of the `ChunkRenderPlugin`, which has to be added too
+ * if there is a `WorldStorage`, chunks are loaded from it and the edited ones are saved to it
+ */
+pub struct ChunkStreamingPlugin;
+
+impl Plugin for ChunkStreamingPlugin {
+  fn build(&self, app: &mut App) {
+    app
+      .init_resource::<BlockRegistry>()
+      .init_resource::<ChunkMap>()
+      .init_resource::<WorldGenerator>()
+      .init_resource::<ChunkMesher>()
+      .init_resource::<ChunkEntities>()
+      .init_resource::<StreamingSettings>()
+      .init_resource::<ViewerChunks>()
+      .init_resource::<SharedRegistry>()
+      .init_resource::<PendingSave>()
+      .add_systems(
+        (
+          update_viewer_chunks,
+          load_chunks,
+          unload_chunks,
+          // the chunks despawned above must be gone before their mesh tasks are applied
+          apply_system_buffers,
+          propagate_light,
+          update_chunk_lods,
+          share_registry,
+          mesh_dirty_chunks,
+          // the tasks replaced above must be in place before the finished ones are removed
+          apply_system_buffers,
+          apply_chunk_meshes,
+        )
+          .chain(),
+      )
+      .add_startup_system(setup_chunk_diagnostics)
+      .add_system(measure_chunks)
+      .add_system(save_chunks_on_exit.in_base_set(CoreSet::Last));
+  }
+}
+
+fn setup_chunk_diagnostics(mut diagnostics: ResMut<Diagnostics>) {
+  diagnostics.add(Diagnostic::new(LOADED_CHUNKS, "loaded_chunks", 20));
+  diagnostics.add(Diagnostic::new(CHUNK_MEMORY, "chunk_memory", 20).with_suffix("KiB"));
+}
+
+fn measure_chunks(mut diagnostics: ResMut<Diagnostics>, chunk_map: Res<ChunkMap>) {
+  diagnostics.add_measurement(LOADED_CHUNKS, || chunk_map.len() as f64);
+  diagnostics.add_measurement(CHUNK_MEMORY, || chunk_map.memory_usage() as f64 / 1024.0);
+}
+
+fn update_viewer_chunks(
+  mut viewer_chunks: ResMut<ViewerChunks>,
+  viewers: Query<&GlobalTransform, With<ChunkViewer>>,
+) {
+  viewer_chunks.centers = viewers
+    .iter()
+    .map(|transform| {
+      (transform.translation() / CHUNK_SIZE as f32)
+        .floor()
+        .as_ivec3()
+    })
+    .collect();
+}
+
+/**
+ * Returns wether the chunk at `coord` should be loaded for a viewer in `center`
+ * `margin`: extra distance (in chunks) added to the radius, used to avoid loading and unloading chunks on the same border
+ */
+fn in_range(coord: IVec3, center: IVec3, settings: &StreamingSettings, margin: i32) -> bool {
+  let offset = coord - center;
+  let radius = settings.radius + margin;
+
+  offset.x * offset.x + offset.z * offset.z <= radius * radius
+    && offset.y.abs() <= settings.vertical_radius + margin
+}
+
+/**
+ * Returns the squared distance (in chunks) from a chunk to the closest viewer
+ */
+fn viewer_distance(coord: IVec3, centers: &[IVec3]) -> i32 {
+  centers
+    .iter()
+    .map(|center| {
+      let offset = coord - *center;
+      offset.dot(offset)
+    })
+    .min()
+    .unwrap_or(i32::MAX)
+}
+
+/**
+ * Returns the level of detail a chunk should be meshed at, given how far it is from the closest viewer
+ */
+fn chunk_lod(coord: IVec3, centers: &[IVec3], settings: &StreamingSettings) -> u32 {
+  let distance = viewer_distance(coord, centers);
+  let lod = settings
+    .lod_distances
+    .iter()
+    .filter(|lod_distance| distance >= *lod_distance * *lod_distance)
+    .count() as u32;
+
+  // chunks can't be merged into less than one voxel
+  lod.min(MAX_LOD).min(CHUNK_SIZE.ilog2())
+}
+
+/**
+ * Returns the sides of a chunk (by direction) touching a chunk meshed at a different level of detail
+ */
+fn lod_transitions(
+  coord: IVec3,
+  lod: u32,
+  centers: &[IVec3],
+  settings: &StreamingSettings,
+) -> [bool; 6] {
+  let mut transitions = [false; 6];
+  for (direction, transition) in transitions.iter_mut().enumerate() {
+    *transition = chunk_lod(coord + direction_offset(direction), centers, settings) != lod;
+  }
+  transitions
+}
+
+/**
+ * Loads the missing chunks around the viewers, closest first
+ * chunks are read from the `WorldStorage` if they were saved, otherwise they are generated (see `WorldGenerator`)
+ */
+fn load_chunks(
+  mut commands: Commands,
+  mut chunk_map: ResMut<ChunkMap>,
+  mut chunk_entities: ResMut<ChunkEntities>,
+  generator: Res<WorldGenerator>,
+  storage: Option<Res<WorldStorage>>,
+  settings: Res<StreamingSettings>,
+  viewer_chunks: Res<ViewerChunks>,
+) {
+  let centers = &viewer_chunks.centers;
+
+  let mut missing = HashSet::new();
+  for center in centers.iter() {
+    let (radius, vertical_radius) = (settings.radius, settings.vertical_radius);
+
+    for x in -radius..=radius {
+      for y in -vertical_radius..=vertical_radius {
+        for z in -radius..=radius {
+          let coord = *center + IVec3::new(x, y, z);
+
+          if in_range(coord, *center, &settings, 0) && !chunk_map.contains(coord) {
+            missing.insert(coord);
+          }
+        }
+      }
+    }
+  }
+
+  let mut missing: Vec<IVec3> = missing.into_iter().collect();
+  missing.sort_by_key(|coord| viewer_distance(*coord, centers));
+
+  for coord in missing.into_iter().take(settings.load_budget) {
+    let saved = storage.as_ref().and_then(|storage| {
+      storage.load_chunk(coord).unwrap_or_else(|error| {
+        error!("failed to load chunk {coord}, generating it again: {error}");
+        None
+      })
+    });
+    chunk_map.insert(coord, saved.unwrap_or_else(|| generator.generate(coord)));
+
+    // transparent meshes are sorted by the distance of their entity, so they need one of their own
+    let transparent = commands.spawn(SpatialBundle::default()).id();
+    let entity = commands
+      .spawn((
+        ChunkEntity {
+          coord,
+          transparent,
+          lod: 0,
+        },
+        SpatialBundle::from_transform(Transform::from_translation(
+          ChunkMap::chunk_origin(coord).as_vec3(),
+        )),
+      ))
+      .add_child(transparent)
+      .id();
+    chunk_entities.entities.insert(coord, entity);
+  }
+}
+
+/**
+ * Takes the result of the `PendingSave` once its task is done
+ * if the save failed the chunks are marked as modified again, so that saving them is tried again on a later frame
+ * `wait`: wether to block until the task is done, otherwise a task still running is left alone
+ */
+fn finish_save(pending_save: &mut PendingSave, chunk_map: &mut ChunkMap, wait: bool) {
+  let Some(task) = pending_save.task.as_mut() else {
+    return;
+  };
+  let result = if wait {
+    future::block_on(task)
+  } else {
+    let Some(result) = future::block_on(future::poll_once(task)) else {
+      return;
+    };
+    result
+  };
+
+  pending_save.task = None;
+  let coords = std::mem::take(&mut pending_save.coords);
+  if let Err(error) = result {
+    error!("failed to save chunks, keeping them loaded: {error}");
+    coords
+      .into_iter()
+      .for_each(|coord| chunk_map.mark_modified(coord));
+  }
+}
+
+/**
+ * Despawns the chunks that are out of range of every viewer
+ * the edited ones are saved to the `WorldStorage` on the `IoTaskPool` first, and only unloaded once they are saved
+ * despawning a chunk drops its `ChunkMeshTask`, which cancels the meshing
+ */
+fn unload_chunks(
+  mut commands: Commands,
+  mut chunk_map: ResMut<ChunkMap>,
+  mut chunk_entities: ResMut<ChunkEntities>,
+  mut pending_save: ResMut<PendingSave>,
+  storage: Option<Res<WorldStorage>>,
+  settings: Res<StreamingSettings>,
+  viewer_chunks: Res<ViewerChunks>,
+) {
+  let centers = &viewer_chunks.centers;
+  finish_save(&mut pending_save, &mut chunk_map, false);
+
+  let out_of_range = chunk_entities
+    .entities
+    .keys()
+    .filter(|coord| {
+      !centers
+        .iter()
+        .any(|center| in_range(**coord, *center, &settings, 1))
+        && !pending_save.coords.contains(coord)
+    })
+    .take(settings.unload_budget)
+    .copied();
+
+  // without a `WorldStorage` the edits are lost
+  let (modified, unmodified): (Vec<IVec3>, Vec<IVec3>) =
+    out_of_range.partition(|coord| storage.is_some() && chunk_map.is_modified(*coord));
+
+  if let Some(storage) = storage {
+    if pending_save.task.is_none() && !modified.is_empty() {
+      let chunks: Vec<(IVec3, Chunk)> = modified
+        .iter()
+        .filter_map(|coord| chunk_map.get(*coord).map(|chunk| (*coord, chunk.clone())))
+        .collect();
+      modified
+        .iter()
+        .for_each(|coord| chunk_map.clear_modified(*coord));
+
+      let storage = storage.clone();
+      pending_save.task = Some(IoTaskPool::get().spawn(async move {
+        storage.save_chunks(chunks.iter().map(|(coord, chunk)| (*coord, chunk)))
+      }));
+      pending_save.coords = modified;
+    }
+  }
+
+  for coord in unmodified {
+    chunk_map.remove(coord);
+
+    if let Some(entity) = chunk_entities.entities.remove(&coord) {
+      commands.entity(entity).despawn_recursive();
+    }
+  }
+}
+
+/**
+ * Lights the chunks that have just been loaded and updates the light around the edited voxels, before the chunks are meshed
+ */
+fn propagate_light(mut chunk_map: ResMut<ChunkMap>, registry: Res<BlockRegistry>) {
+  update_light(&mut chunk_map, &registry);
+}
+
+/**
+ * Remeshes the chunks whose level of detail changed as the viewers moved,
+ * along with their neighbours, which may have to close their sides facing them (see `PaddedChunk::downsampled`)
+ */
+fn update_chunk_lods(
+  mut chunk_map: ResMut<ChunkMap>,
+  chunks: Query<(&ChunkEntity, Option<&ChunkMeshTask>)>,
+  settings: Res<StreamingSettings>,
+  viewer_chunks: Res<ViewerChunks>,
+) {
+  for (chunk, task) in &chunks {
+    // the meshes being built are already at the right level of detail
+    let lod = task.map_or(chunk.lod, |task| task.lod);
+    if chunk_lod(chunk.coord, &viewer_chunks.centers, &settings) == lod {
+      continue;
+    }
+
+    chunk_map.mark_dirty(chunk.coord);
+    for direction in 0..6 {
+      chunk_map.mark_dirty(chunk.coord + direction_offset(direction));
+    }
+  }
+}
+
+/**
+ * Saves every edited chunk to the `WorldStorage` when the app is closing, after the `PendingSave` is done
+ */
+fn save_chunks_on_exit(
+  mut exit: EventReader<AppExit>,
+  mut chunk_map: ResMut<ChunkMap>,
+  mut pending_save: ResMut<PendingSave>,
+  storage: Option<Res<WorldStorage>>,
+) {
+  let Some(storage) = storage else { return };
+  if exit.iter().last().is_none() {
+    return;
+  }
+  finish_save(&mut pending_save, &mut chunk_map, true);
+
+  let modified: Vec<IVec3> = chunk_map.modified_chunks().collect();
+  let chunks = modified
+    .iter()
+    .filter_map(|coord| chunk_map.get(*coord).map(|chunk| (*coord, chunk)));
+
+  match storage.save_chunks(chunks) {
+    Ok(()) => modified
+      .iter()
+      .for_each(|coord| chunk_map.clear_modified(*coord)),
+    Err(error) => error!("failed to save the world: {error}"),
+  }
+}
+
+fn share_registry(registry: Res<BlockRegistry>, mut shared: ResMut<SharedRegistry>) {
+  if registry.is_changed() {
+    shared.0 = Arc::new(registry.clone());
+  }
+}
+
+/**
+ * Sends the dirty chunks to be meshed on the `AsyncComputeTaskPool`, closest to the viewers first
+ * only the copy of the voxels (see `PaddedChunk`) is made on the main thread, at the level of detail of the chunk
+ */
+fn mesh_dirty_chunks(
+  mut commands: Commands,
+  mut chunk_map: ResMut<ChunkMap>,
+  chunk_entities: Res<ChunkEntities>,
+  registry: Res<SharedRegistry>,
+  mesher: Res<ChunkMesher>,
+  settings: Res<StreamingSettings>,
+  viewer_chunks: Res<ViewerChunks>,
+) {
+  let centers = &viewer_chunks.centers;
+
+  let mut dirty: Vec<IVec3> = chunk_map.dirty_chunks().collect();
+  if dirty.is_empty() {
+    return;
+  }
+  dirty.sort_by_key(|coord| viewer_distance(*coord, centers));
+
+  let task_pool = AsyncComputeTaskPool::get();
+  let registry = registry.0.clone();
+
+  for coord in dirty.into_iter().take(settings.mesh_budget) {
+    chunk_map.clear_dirty(coord);
+
+    let Some(entity) = chunk_entities.get(coord) else {
+      continue;
+    };
+
+    let lod = chunk_lod(coord, centers, &settings);
+    let padded = if lod == 0 {
+      chunk_map.padded_chunk(coord)
+    } else {
+      let transitions = lod_transitions(coord, lod, centers, &settings);
+      chunk_map.downsampled_chunk(coord, lod, transitions, &registry)
+    };
+    let Some(padded) = padded else {
+      continue;
+    };
+
+    let (registry, mesher) = (registry.clone(), mesher.0.clone());
+    let task = task_pool.spawn(async move { mesher.mesh(&padded, &registry) });
+
+    // replaces (and cancels) the task of a previous edit that is still running
+    commands.entity(entity).insert(ChunkMeshTask { task, lod });
+  }
+}
+
+/**
+ * Swaps the meshes of the finished mesh tasks into their chunk entities
+ * chunks that already have a mesh get it replaced in place, so their `Handle<Mesh>` stays the same
+ * the entities are scaled by the level of detail of their meshes, whose voxels are bigger than the ones of the chunk
+ */
+fn apply_chunk_meshes(
+  mut commands: Commands,
+  mut meshes: ResMut<Assets<Mesh>>,
+  materials: Res<ChunkMaterials>,
+  mut tasks: Query<(Entity, &mut ChunkEntity, &mut ChunkMeshTask, &mut Transform)>,
+  mesh_handles: Query<&Handle<Mesh>>,
+) {
+  for (entity, mut chunk, mut task, mut transform) in tasks.iter_mut() {
+    let Some(chunk_meshes) = future::block_on(future::poll_once(&mut task.task)) else {
+      continue;
+    };
+
+    commands.entity(entity).remove::<ChunkMeshTask>();
+    chunk.lod = task.lod;
+    transform.scale = Vec3::splat(lod_scale(task.lod) as f32);
+
+    set_chunk_mesh(
+      &mut commands,
+      &mut meshes,
+      entity,
+      mesh_handles.get(entity).ok(),
+      chunk_meshes.opaque,
+      &materials.opaque,
+    );
+    set_chunk_mesh(
+      &mut commands,
+      &mut meshes,
+      chunk.transparent,
+      mesh_handles.get(chunk.transparent).ok(),
+      chunk_meshes.transparent,
+      &materials.transparent,
+    );
+  }
+}
+
+/**
+ * Gives a mesh to an entity, replacing its current mesh in place if it has one
+ * `handle`: the current mesh of the entity
+ */
+fn set_chunk_mesh(
+  commands: &mut Commands,
+  meshes: &mut Assets<Mesh>,
+  entity: Entity,
+  handle: Option<&Handle<Mesh>>,
+  mesh: Mesh,
+  material: &Handle<ChunkMaterial>,
+) {
+  // chunks without any face (e.g. air) don't need a mesh at all
+  if mesh.count_vertices() == 0 {
+    commands.entity(entity).remove::<Handle<Mesh>>();
+    return;
+  }
+
+  if let Some(existing) = handle.and_then(|handle| meshes.get_mut(handle)) {
+    *existing = mesh;
+    return;
+  }
+
+  commands
+    .entity(entity)
+    .insert((meshes.add(mesh), material.clone()));
+}
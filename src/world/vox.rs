@@ -0,0 +1,784 @@
+/*!
+ * MagicaVoxel `.vox` models, to author props outside of the game and place them in the world
+ *
+ * a `.vox` file starts with the magic bytes `VOX ` and a version, followed by a `MAIN` chunk containing every other chunk
+ * each chunk is an id (4 bytes), the length of its content and the length of its children (2 little endian `u32`), then its content:
+ * - `SIZE` and `XYZI`: the size of a model and its voxels (`x y z color`, 1 byte each), a pair for every model
+ * - `RGBA`: the palette, the color of index `i` is the `i - 1`th entry, files without one use the default palette of MagicaVoxel
+ * - `nTRN`, `nGRP` and `nSHP`: the scene graph, transforms, groups and shapes placing the models, files without one have a model at the origin
+ *
+ * the other chunks (materials, layers, cameras...) are skipped
+ * MagicaVoxel uses `z` as the up axis, the voxels are converted so that `y` is up, with the same handedness
+ */
+
+use std::fs;
+use std::io::{self, Cursor, Read};
+use std::path::Path;
+
+use bevy::prelude::*;
+use bevy::utils::{HashMap, HashSet};
+
+use super::chunkmap::{ChunkMap, CHUNK_SIZE};
+use crate::block::{blocks, BlockId, BlockProperties, BlockRegistry, BlockTextures};
+use crate::geometry::chunk::Chunk;
+
+const MAGIC: &[u8; 4] = b"VOX ";
+
+fn invalid_data(message: String) -> io::Error {
+  io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+fn read_u32(reader: &mut impl Read) -> io::Result<u32> {
+  let mut bytes = [0; 4];
+  reader.read_exact(&mut bytes)?;
+  Ok(u32::from_le_bytes(bytes))
+}
+
+fn read_i32(reader: &mut impl Read) -> io::Result<i32> {
+  Ok(read_u32(reader)? as i32)
+}
+
+fn remaining(reader: &Cursor<Vec<u8>>) -> usize {
+  reader
+    .get_ref()
+    .len()
+    .saturating_sub(reader.position() as usize)
+}
+
+/**
+ * Reads `length` bytes, the lengths come from the file so they are checked against what is left before allocating
+ */
+fn read_bytes(reader: &mut Cursor<Vec<u8>>, length: u32) -> io::Result<Vec<u8>> {
+  if length as usize > remaining(reader) {
+    return Err(invalid_data(format!(
+      "length {length} past the end of the data"
+    )));
+  }
+  let mut bytes = vec![0; length as usize];
+  reader.read_exact(&mut bytes)?;
+  Ok(bytes)
+}
+
+/**
+ * Reads the amount of items of a list, fails if that many items of at least `item_length` bytes can't fit in what is left
+ */
+fn read_count(reader: &mut Cursor<Vec<u8>>, item_length: usize) -> io::Result<u32> {
+  let count = read_u32(reader)?;
+  if count as usize > remaining(reader) / item_length {
+    return Err(invalid_data(format!(
+      "count {count} past the end of the data"
+    )));
+  }
+  Ok(count)
+}
+
+fn read_string(reader: &mut Cursor<Vec<u8>>) -> io::Result<String> {
+  let length = read_u32(reader)?;
+  Ok(String::from_utf8_lossy(&read_bytes(reader, length)?).into_owned())
+}
+
+/**
+ * Reads a dictionary of the scene graph: a count, then pairs of strings (each a length and its bytes)
+ */
+fn read_dict(reader: &mut Cursor<Vec<u8>>) -> io::Result<HashMap<String, String>> {
+  (0..read_count(reader, 8)?)
+    .map(|_| Ok((read_string(reader)?, read_string(reader)?)))
+    .collect()
+}
+
+/**
+ * Returns the default palette of MagicaVoxel: a 6x6x6 color cube without black, then ramps of red, green, blue and gray
+ */
+fn default_palette() -> [Color; 256] {
+  let steps = [0xff, 0xcc, 0x99, 0x66, 0x33, 0x00];
+  let ramp = [0xee, 0xdd, 0xbb, 0xaa, 0x88, 0x77, 0x55, 0x44, 0x22, 0x11];
+
+  let cube = steps
+    .into_iter()
+    .flat_map(move |r| {
+      steps
+        .into_iter()
+        .flat_map(move |g| steps.into_iter().map(move |b| [r, g, b]))
+    })
+    .filter(|color| *color != [0, 0, 0]);
+  let ramps = [[1, 0, 0], [0, 1, 0], [0, 0, 1], [1, 1, 1]]
+    .into_iter()
+    .flat_map(|channels: [u8; 3]| {
+      ramp
+        .iter()
+        .map(move |value| channels.map(|channel| channel * value))
+    });
+
+  let mut palette = [Color::NONE; 256];
+  for (entry, [r, g, b]) in palette.iter_mut().skip(1).zip(cube.chain(ramps)) {
+    *entry = Color::rgb_u8(r, g, b);
+  }
+  palette
+}
+
+/**
+ * Returns the rotation encoded in a byte of the scene graph
+ * bits 0-1 and 2-3 are the columns of the non zero entries of the first two rows (the third row gets the last column),
+ * bits 4, 5 and 6 are set when the entries of the rows are -1 instead of 1
+ */
+fn decode_rotation(byte: u8) -> io::Result<Mat3> {
+  let first = (byte & 3) as usize;
+  let second = (byte >> 2 & 3) as usize;
+  if first == second || first > 2 || second > 2 {
+    return Err(invalid_data(format!("invalid rotation {byte}")));
+  }
+  let third = 3 - first - second;
+
+  let mut columns = [[0.0; 3]; 3];
+  for (row, column) in [first, second, third].into_iter().enumerate() {
+    columns[column][row] = if byte >> (4 + row) & 1 == 1 {
+      -1.0
+    } else {
+      1.0
+    };
+  }
+  Ok(Mat3::from_cols_array_2d(&columns))
+}
+
+/**
+ * A node of the scene graph
+ */
+enum Node {
+  Transform {
+    child: i32,
+    rotation: Mat3,
+    translation: IVec3,
+    hidden: bool,
+  },
+  Group(Vec<i32>),
+  Shape(Vec<usize>),
+}
+
+/**
+ * A model of a `.vox` file
+ * `size`: the size of the model, in the coordinates of MagicaVoxel (`z` up)
+ * `voxels`: the position of the voxels in the model and the index of their color in the palette (never 0)
+ */
+#[derive(Clone, Debug)]
+pub struct VoxModel {
+  pub size: IVec3,
+  pub voxels: Vec<(IVec3, u8)>,
+}
+
+/**
+ * A copy of a model placed in the scene
+ * `rotation`, `translation`: where the center of the model goes, in the coordinates of MagicaVoxel
+ */
+#[derive(Clone, Debug)]
+pub struct VoxInstance {
+  pub model: usize,
+  pub rotation: Mat3,
+  pub translation: IVec3,
+}
+
+/**
+ * How the colors of a `.vox` palette become blocks (see `VoxScene::block_palette`)
+ * `Nearest`: every color becomes the visible registered block with the closest color
+ * `Colors`: every color gets a block of its own, so the voxels keep their exact colors,
+ * the blocks are registered as `vox_rrggbbaa` (and reused by later imports), once the registry is full the closest block is used instead
+ * each new block gets a new tile of the atlas, the atlas generated from the colors of the blocks (see `generate_atlas`) shows them
+ * only if they are registered before the `ChunkRenderPlugin` starts up, a custom `BlockAtlas` has to provide the tiles itself
+ */
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum PaletteMapping {
+  Nearest,
+  Colors,
+}
+
+/**
+ * The content of a `.vox` file
+ * `models`: the models of the file
+ * `palette`: the color of each index of the palette, index 0 is unused (empty voxels are not stored)
+ * `instances`: the visible models of the scene, with their transforms resolved through the scene graph
+ */
+#[derive(Clone, Debug)]
+pub struct VoxScene {
+  pub models: Vec<VoxModel>,
+  pub palette: [Color; 256],
+  pub instances: Vec<VoxInstance>,
+}
+
+impl VoxScene {
+  /**
+   * Reads a `.vox` file
+   */
+  pub fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+    Self::read(&mut Cursor::new(fs::read(path)?))
+  }
+
+  /**
+   * Reads a `.vox` file from a reader
+   */
+  pub fn read(reader: &mut impl Read) -> io::Result<Self> {
+    let mut magic = [0; 4];
+    reader.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+      return Err(invalid_data("not a .vox file".to_string()));
+    }
+    let _version = read_u32(reader)?;
+
+    let mut id = [0; 4];
+    reader.read_exact(&mut id)?;
+    if &id != b"MAIN" {
+      return Err(invalid_data("the file has no MAIN chunk".to_string()));
+    }
+    let content = read_u32(reader)?;
+    let children = read_u32(reader)?;
+    io::copy(&mut reader.take(content as u64), &mut io::sink())?;
+
+    let mut bytes = Vec::new();
+    reader.take(children as u64).read_to_end(&mut bytes)?;
+    let mut reader = Cursor::new(bytes);
+
+    let mut models = Vec::new();
+    let mut size = None;
+    let mut palette = default_palette();
+    let mut nodes = HashMap::default();
+
+    while (reader.position() as usize) < reader.get_ref().len() {
+      reader.read_exact(&mut id)?;
+      let content = read_u32(&mut reader)?;
+      let children = read_u32(&mut reader)?;
+
+      let bytes = read_bytes(&mut reader, content)?;
+      reader.set_position(reader.position() + children as u64);
+      let chunk = &mut Cursor::new(bytes);
+
+      match &id {
+        b"SIZE" => {
+          let [x, y, z] = [(); 3].map(|_| read_i32(chunk));
+          size = Some(IVec3::new(x?, y?, z?));
+        }
+        b"XYZI" => {
+          let size = size
+            .take()
+            .ok_or(invalid_data("XYZI chunk without a SIZE chunk".to_string()))?;
+          let voxels = (0..read_count(chunk, 4)?)
+            .map(|_| {
+              let mut voxel = [0; 4];
+              chunk.read_exact(&mut voxel)?;
+              let [x, y, z, color] = voxel;
+              Ok((IVec3::new(x as i32, y as i32, z as i32), color))
+            })
+            .collect::<io::Result<_>>()?;
+          models.push(VoxModel { size, voxels });
+        }
+        b"RGBA" => {
+          for entry in palette.iter_mut().skip(1) {
+            let mut color = [0; 4];
+            chunk.read_exact(&mut color)?;
+            let [r, g, b, a] = color;
+            *entry = Color::rgba_u8(r, g, b, a);
+          }
+        }
+        b"nTRN" => {
+          let node = read_i32(chunk)?;
+          let hidden = read_dict(chunk)?
+            .get("_hidden")
+            .is_some_and(|hidden| hidden == "1");
+          let child = read_i32(chunk)?;
+          let _reserved = read_i32(chunk)?;
+          let _layer = read_i32(chunk)?;
+
+          // animated transforms have several frames, only the first one is used
+          let frames = read_u32(chunk)?;
+          let frame = if frames > 0 {
+            read_dict(chunk)?
+          } else {
+            HashMap::default()
+          };
+
+          let rotation = match frame.get("_r") {
+            Some(rotation) => decode_rotation(
+              rotation
+                .parse()
+                .map_err(|_| invalid_data(format!("invalid rotation {rotation}")))?,
+            )?,
+            None => Mat3::IDENTITY,
+          };
+          let translation = match frame.get("_t") {
+            Some(translation) => {
+              let values: Vec<i32> = translation
+                .split_whitespace()
+                .filter_map(|value| value.parse().ok())
+                .collect();
+              let [x, y, z] = values[..] else {
+                return Err(invalid_data(format!("invalid translation {translation}")));
+              };
+              IVec3::new(x, y, z)
+            }
+            None => IVec3::ZERO,
+          };
+
+          nodes.insert(
+            node,
+            Node::Transform {
+              child,
+              rotation,
+              translation,
+              hidden,
+            },
+          );
+        }
+        b"nGRP" => {
+          let node = read_i32(chunk)?;
+          read_dict(chunk)?;
+          let children = (0..read_count(chunk, 4)?)
+            .map(|_| read_i32(chunk))
+            .collect::<io::Result<_>>()?;
+          nodes.insert(node, Node::Group(children));
+        }
+        b"nSHP" => {
+          let node = read_i32(chunk)?;
+          read_dict(chunk)?;
+          // a model index and its dictionary
+          let models = (0..read_count(chunk, 8)?)
+            .map(|_| {
+              let model = read_u32(chunk)? as usize;
+              read_dict(chunk)?;
+              Ok(model)
+            })
+            .collect::<io::Result<_>>()?;
+          nodes.insert(node, Node::Shape(models));
+        }
+        _ => {}
+      }
+    }
+
+    let mut instances = Vec::new();
+    if nodes.is_empty() {
+      // without a scene graph the corner of every model is at the origin
+      for (model, data) in models.iter().enumerate() {
+        instances.push(VoxInstance {
+          model,
+          rotation: Mat3::IDENTITY,
+          translation: data.size / 2,
+        });
+      }
+    } else {
+      // the nodes are walked from the root, shapes may be shared but transforms and groups are visited once,
+      // reaching one twice means it is shared or part of a cycle, which would make the walk exponential or endless
+      let mut visited = HashSet::default();
+      let mut stack = vec![(0, Mat3::IDENTITY, IVec3::ZERO)];
+      while let Some((node, rotation, translation)) = stack.pop() {
+        let parent = matches!(
+          nodes.get(&node),
+          Some(Node::Transform { .. } | Node::Group(_))
+        );
+        if parent && !visited.insert(node) {
+          return Err(invalid_data(format!(
+            "the node {node} is reached twice in the scene graph"
+          )));
+        }
+
+        match nodes.get(&node) {
+          Some(Node::Transform {
+            child,
+            rotation: local_rotation,
+            translation: local_translation,
+            hidden,
+          }) => {
+            if !hidden {
+              let offset = (rotation * local_translation.as_vec3()).round().as_ivec3();
+              stack.push((*child, rotation * *local_rotation, translation + offset));
+            }
+          }
+          Some(Node::Group(children)) => {
+            stack.extend(children.iter().map(|child| (*child, rotation, translation)));
+          }
+          Some(Node::Shape(shapes)) => {
+            for model in shapes {
+              if *model >= models.len() {
+                return Err(invalid_data(format!(
+                  "the scene uses the missing model {model}"
+                )));
+              }
+              instances.push(VoxInstance {
+                model: *model,
+                rotation,
+                translation,
+              });
+            }
+          }
+          None => {
+            return Err(invalid_data(format!(
+              "the scene uses the missing node {node}"
+            )))
+          }
+        }
+      }
+    }
+
+    Ok(Self {
+      models,
+      palette,
+      instances,
+    })
+  }
+
+  /**
+   * Iterates over the voxels of every instance of the scene, yields `(position, color index)`
+   * the positions are in the coordinates of the game (`y` up), the center of the scene is around the origin
+   */
+  pub fn voxels(&self) -> impl Iterator<Item = (IVec3, u8)> + '_ {
+    self.instances.iter().flat_map(|instance| {
+      let model = &self.models[instance.model];
+      let pivot = model.size / 2;
+
+      model.voxels.iter().map(move |(voxel, color)| {
+        let p = instance.translation
+          + (instance.rotation * (*voxel - pivot).as_vec3())
+            .round()
+            .as_ivec3();
+        (IVec3::new(p.x, p.z, -p.y - 1), *color)
+      })
+    })
+  }
+
+  /**
+   * Returns the block of every index of the palette, index 0 is air
+   * only the colors used by the voxels of the scene are mapped, the other indices are air
+   * `registry`: the blocks to pick from, blocks may be added to it with `PaletteMapping::Colors`
+   * `mapping`: how the colors become blocks
+   */
+  pub fn block_palette(
+    &self,
+    registry: &mut BlockRegistry,
+    mapping: PaletteMapping,
+  ) -> [BlockId; 256] {
+    let mut used = [false; 256];
+    for model in &self.models {
+      for (_, color) in &model.voxels {
+        used[*color as usize] = true;
+      }
+    }
+
+    // the new blocks get the tiles after the last one used by the registry
+    let mut tile = registry
+      .iter()
+      .flat_map(|(_, block)| block.textures.0)
+      .max()
+      .map_or(0, |tile| tile + 1);
+
+    let mut blocks = [blocks::AIR; 256];
+    for (index, color) in self.palette.iter().enumerate().skip(1) {
+      if !used[index] {
+        continue;
+      }
+
+      let [r, g, b, a] = color
+        .as_rgba_f32()
+        .map(|channel| (channel * 255.0).round() as u8);
+      let name = format!("vox_{r:02x}{g:02x}{b:02x}{a:02x}");
+      blocks[index] = match (mapping, registry.id(&name)) {
+        (PaletteMapping::Colors, Some(block)) => block,
+        (PaletteMapping::Colors, None) if registry.len() <= BlockId::MAX as usize => {
+          let block = if a == u8::MAX {
+            BlockProperties::opaque(&name, *color)
+          } else {
+            BlockProperties::transparent(&name, *color)
+          };
+          let textures = BlockTextures::all(tile);
+          tile += 1;
+          registry.register(block.with_textures(textures))
+        }
+        _ => nearest_block(*color, registry),
+      };
+    }
+
+    blocks
+  }
+
+  /**
+   * Writes the voxels of the scene into new chunks, returns them by chunk coordinates (see `ChunkMap::chunk_coord`)
+   * `blocks`: the block of every index of the palette (see `block_palette`), voxels mapped to air are skipped
+   * `origin`: where the center of the scene is placed in the world
+   */
+  pub fn to_chunks(&self, blocks: &[BlockId; 256], origin: IVec3) -> HashMap<IVec3, Chunk> {
+    let mut chunks: HashMap<IVec3, Chunk> = HashMap::default();
+    for (voxel, color) in self.voxels() {
+      let block = blocks[color as usize];
+      if block == blocks::AIR {
+        continue;
+      }
+
+      let voxel = origin + voxel;
+      let (x, y, z) = ChunkMap::local_coord(voxel);
+      chunks
+        .entry(ChunkMap::chunk_coord(voxel))
+        .or_insert_with(|| Chunk::new(CHUNK_SIZE))
+        .set(x, y, z, block);
+    }
+
+    chunks
+  }
+
+  /**
+   * Places the voxels of the scene in the loaded chunks of a map, like a prop, returns the amount of voxels placed
+   * the edited chunks are remeshed, relit and saved like any other edit (see `ChunkMap::set_voxel`)
+   * `blocks`: the block of every index of the palette (see `block_palette`), voxels mapped to air are skipped
+   * `origin`: where the center of the scene is placed in the world
+   */
+  pub fn place(&self, map: &mut ChunkMap, blocks: &[BlockId; 256], origin: IVec3) -> usize {
+    self
+      .voxels()
+      .filter(|(_, color)| blocks[*color as usize] != blocks::AIR)
+      .filter(|(voxel, color)| {
+        map
+          .set_voxel(origin + *voxel, blocks[*color as usize])
+          .is_some()
+      })
+      .count()
+  }
+}
+
+/**
+ * Returns the visible block with the color closest to `color`, or air if there are none
+ */
+fn nearest_block(color: Color, registry: &BlockRegistry) -> BlockId {
+  let color = Vec4::from(color.as_rgba_f32());
+  registry
+    .iter()
+    .filter(|(_, block)| block.is_visible())
+    .min_by(|(_, a), (_, b)| {
+      let distance =
+        |block: &BlockProperties| Vec4::from(block.color.as_rgba_f32()).distance_squared(color);
+      distance(a).total_cmp(&distance(b))
+    })
+    .map_or(blocks::AIR, |(id, _)| id)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /**
+   * Writes a chunk of a `.vox` file
+   */
+  fn chunk(id: &[u8; 4], content: &[u8], children: &[u8]) -> Vec<u8> {
+    let mut bytes = id.to_vec();
+    bytes.extend((content.len() as u32).to_le_bytes());
+    bytes.extend((children.len() as u32).to_le_bytes());
+    bytes.extend(content);
+    bytes.extend(children);
+    bytes
+  }
+
+  fn words(values: &[i32]) -> Vec<u8> {
+    values
+      .iter()
+      .flat_map(|value| value.to_le_bytes())
+      .collect()
+  }
+
+  fn dict(pairs: &[(&str, &str)]) -> Vec<u8> {
+    let mut bytes = words(&[pairs.len() as i32]);
+    for string in pairs.iter().flat_map(|(key, value)| [key, value]) {
+      bytes.extend(words(&[string.len() as i32]));
+      bytes.extend(string.as_bytes());
+    }
+    bytes
+  }
+
+  fn vox_file(children: &[Vec<u8>]) -> Vec<u8> {
+    let mut bytes = MAGIC.to_vec();
+    bytes.extend(words(&[150]));
+    bytes.extend(chunk(b"MAIN", &[], &children.concat()));
+    bytes
+  }
+
+  /**
+   * A 2x2x1 model with 2 voxels: red at (0, 0, 0) and green at (1, 0, 0)
+   */
+  fn model() -> Vec<Vec<u8>> {
+    let mut palette = vec![0; 256 * 4];
+    palette[0..4].copy_from_slice(&[255, 0, 0, 255]);
+    palette[4..8].copy_from_slice(&[0, 255, 0, 255]);
+
+    vec![
+      chunk(b"SIZE", &words(&[2, 2, 1]), &[]),
+      chunk(
+        b"XYZI",
+        &[words(&[2]), vec![0, 0, 0, 1, 1, 0, 0, 2]].concat(),
+        &[],
+      ),
+      chunk(b"RGBA", &palette, &[]),
+    ]
+  }
+
+  #[test]
+  fn reads_models_and_palette() {
+    let scene = VoxScene::read(&mut Cursor::new(vox_file(&model()))).unwrap();
+
+    assert_eq!(scene.models.len(), 1);
+    assert_eq!(scene.models[0].size, IVec3::new(2, 2, 1));
+    assert_eq!(scene.palette[1], Color::rgba_u8(255, 0, 0, 255));
+    assert_eq!(scene.palette[2], Color::rgba_u8(0, 255, 0, 255));
+
+    // without a scene graph the model starts at the origin, the y of the file becomes -z
+    let voxels: Vec<_> = scene.voxels().collect();
+    assert_eq!(
+      voxels,
+      vec![(IVec3::new(0, 0, -1), 1), (IVec3::new(1, 0, -1), 2)]
+    );
+
+    assert!(VoxScene::read(&mut Cursor::new(b"VOXR".to_vec())).is_err());
+  }
+
+  #[test]
+  fn places_instances_through_the_scene_graph() {
+    // a group with two copies of the model, one moved along x and one turned by 90 degrees around z
+    let turned = 1 | 1 << 4;
+    let mut children = model();
+    children.extend([
+      chunk(
+        b"nTRN",
+        &[words(&[0]), dict(&[]), words(&[1, -1, -1, 1]), dict(&[])].concat(),
+        &[],
+      ),
+      chunk(
+        b"nGRP",
+        &[words(&[1]), dict(&[]), words(&[2, 2, 4])].concat(),
+        &[],
+      ),
+      chunk(
+        b"nTRN",
+        &[
+          words(&[2]),
+          dict(&[]),
+          words(&[3, -1, -1, 1]),
+          dict(&[("_t", "10 0 0")]),
+        ]
+        .concat(),
+        &[],
+      ),
+      chunk(
+        b"nTRN",
+        &[
+          words(&[4]),
+          dict(&[]),
+          words(&[3, -1, -1, 1]),
+          dict(&[("_r", &turned.to_string())]),
+        ]
+        .concat(),
+        &[],
+      ),
+      chunk(
+        b"nSHP",
+        &[words(&[3]), dict(&[]), words(&[1, 0]), dict(&[])].concat(),
+        &[],
+      ),
+    ]);
+    let scene = VoxScene::read(&mut Cursor::new(vox_file(&children))).unwrap();
+
+    assert_eq!(scene.instances.len(), 2);
+    let mut voxels: Vec<_> = scene.voxels().collect();
+    voxels.sort_by_key(|(voxel, _)| voxel.to_array());
+
+    // the model is centered on (1, 1, 0): the moved copy covers x 9 and 10, the turned one goes along y (the -z of the game)
+    assert_eq!(
+      voxels,
+      vec![
+        (IVec3::new(1, 0, -1), 2),
+        (IVec3::new(1, 0, 0), 1),
+        (IVec3::new(9, 0, 0), 1),
+        (IVec3::new(10, 0, 0), 2),
+      ]
+    );
+  }
+
+  #[test]
+  fn rejects_lengths_past_the_end_of_the_file() {
+    let mut children = model();
+    children[1][12..16].copy_from_slice(&u32::MAX.to_le_bytes());
+    let error = VoxScene::read(&mut Cursor::new(vox_file(&children))).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+    let mut children = model();
+    children[0][4..8].copy_from_slice(&u32::MAX.to_le_bytes());
+    let error = VoxScene::read(&mut Cursor::new(vox_file(&children))).unwrap_err();
+    assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+  }
+
+  #[test]
+  fn rejects_shared_nodes_and_cycles() {
+    // a group listing the same transform twice, then a transform pointing back at the root
+    let mut shared = model();
+    shared.extend([
+      chunk(
+        b"nGRP",
+        &[words(&[0]), dict(&[]), words(&[2, 1, 1])].concat(),
+        &[],
+      ),
+      chunk(
+        b"nTRN",
+        &[words(&[1]), dict(&[]), words(&[2, -1, -1, 1]), dict(&[])].concat(),
+        &[],
+      ),
+      chunk(
+        b"nSHP",
+        &[words(&[2]), dict(&[]), words(&[1, 0]), dict(&[])].concat(),
+        &[],
+      ),
+    ]);
+    let mut cycle = model();
+    cycle.push(chunk(
+      b"nTRN",
+      &[words(&[0]), dict(&[]), words(&[0, -1, -1, 1]), dict(&[])].concat(),
+      &[],
+    ));
+
+    for children in [shared, cycle] {
+      let error = VoxScene::read(&mut Cursor::new(vox_file(&children))).unwrap_err();
+      assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+  }
+
+  #[test]
+  fn maps_the_palette_to_blocks() {
+    let scene = VoxScene::read(&mut Cursor::new(vox_file(&model()))).unwrap();
+
+    let mut registry = BlockRegistry::default();
+    let nearest = scene.block_palette(&mut registry, PaletteMapping::Nearest);
+    assert_eq!(nearest[1], registry.id("dirt").unwrap());
+    assert_eq!(nearest[2], registry.id("grass").unwrap());
+    assert_eq!(nearest[3], blocks::AIR);
+    assert_eq!(registry.len(), BlockRegistry::default().len());
+
+    let colors = scene.block_palette(&mut registry, PaletteMapping::Colors);
+    assert_eq!(colors[1], registry.id("vox_ff0000ff").unwrap());
+    assert_eq!(
+      registry.get(colors[2]).color,
+      Color::rgba_u8(0, 255, 0, 255)
+    );
+
+    // the new blocks don't share the tiles of the other blocks
+    let tiles = [colors[1], colors[2]].map(|block| registry.get(block).textures.tile(0));
+    assert_ne!(tiles[0], tiles[1]);
+    let default = BlockRegistry::default();
+    let used: Vec<u32> = default
+      .iter()
+      .flat_map(|(_, block)| block.textures.0)
+      .collect();
+    assert!(!tiles.iter().any(|tile| used.contains(tile)));
+    assert_eq!(
+      scene.block_palette(&mut registry, PaletteMapping::Colors),
+      colors
+    );
+
+    // the voxels end up on both sides of a chunk border
+    let chunks = scene.to_chunks(&colors, IVec3::new(CHUNK_SIZE as i32 - 1, 0, 0));
+    assert_eq!(chunks.len(), 2);
+    let far = &chunks[&IVec3::new(0, 0, -1)];
+    assert_eq!(far.get(CHUNK_SIZE - 1, 0, CHUNK_SIZE - 1), colors[1]);
+    assert_eq!(
+      chunks[&IVec3::new(1, 0, -1)].get(0, 0, CHUNK_SIZE - 1),
+      colors[2]
+    );
+  }
+}